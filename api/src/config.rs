@@ -0,0 +1,673 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use minio::s3::http::BaseUrl;
+use serde::Deserialize;
+
+use crate::{imaging::OutputFormat, wechat::DEFAULT_WECHAT_BASE_URL};
+
+/// MinIO 镜像自带的默认账号和密码
+const DEFAULT_MINIO_CREDENTIAL: &str = "minioadmin";
+
+/// 敏感配置项，Debug 输出时打码，使用时通过 `expose` 取出明文
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+impl FromStr for Secret {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+/// 应用配置
+/// 加载顺序（后者覆盖前者）：内置默认值 -> `config.toml` -> `config.{APP_ENV}.toml` -> 环境变量
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// 运行环境，取自 APP_ENV，默认 development
+    pub env: String,
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub storage: StorageSettings,
+    pub minio: MinioSettings,
+    pub jwt: JwtSettings,
+    pub wechat: WechatSettings,
+    pub image: ImageSettings,
+    pub upload: UploadSettings,
+    pub gc: GcSettings,
+    pub publisher: PublisherSettings,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3001,
+        }
+    }
+}
+
+impl ServerSettings {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    /// 连接串中包含密码，按敏感信息处理
+    pub url: Secret,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// MinIO / S3 兼容的对象存储
+    #[default]
+    Minio,
+    /// 本地目录，通过 `/uploads` 对外提供，开发和测试时不需要 MinIO
+    Local,
+}
+
+impl FromStr for StorageBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minio" => Ok(Self::Minio),
+            "local" => Ok(Self::Local),
+            _ => anyhow::bail!("unknown storage backend `{s}`"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    pub backend: StorageBackend,
+    pub local: LocalStorageSettings,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LocalStorageSettings {
+    /// 文件保存目录，同时作为 `/uploads` 的静态目录
+    pub root: String,
+    /// 文件对外访问地址前缀，未配置时使用 `http://{server.host}:{server.port}/uploads`
+    /// block 中的图片必须是完整的 http(s) URL，部署在反向代理后面时需要配置为对外地址
+    pub public_base_url: Option<String>,
+}
+
+impl Default for LocalStorageSettings {
+    fn default() -> Self {
+        Self {
+            root: "./uploads".to_string(),
+            public_base_url: None,
+        }
+    }
+}
+
+impl LocalStorageSettings {
+    pub fn public_url(&self, server: &ServerSettings) -> String {
+        match &self.public_base_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("http://{}/uploads", server.addr()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct MinioSettings {
+    pub endpoint: String,
+    pub access_key: String,
+    pub secret_key: Secret,
+    pub bucket: String,
+    /// 图片对外访问地址前缀，未配置时使用 `{endpoint}/{bucket}`
+    pub public_base_url: Option<String>,
+    /// 预签名上传、下载 URL 的有效期，单位秒
+    pub presign_expiry_secs: u32,
+}
+
+impl Default for MinioSettings {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:9000".to_string(),
+            access_key: String::new(),
+            secret_key: Secret::default(),
+            bucket: "collection".to_string(),
+            public_base_url: None,
+            presign_expiry_secs: 900,
+        }
+    }
+}
+
+impl MinioSettings {
+    pub fn public_url(&self) -> String {
+        match &self.public_base_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("{}/{}", self.endpoint.trim_end_matches('/'), self.bucket),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JwtSettings {
+    /// kid -> 密钥
+    pub keys: HashMap<String, Secret>,
+    /// 当前用于签名的 kid，只有一把密钥时可省略
+    pub active_kid: Option<String>,
+    pub access_ttl_minutes: i64,
+    pub refresh_ttl_days: i64,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            active_kid: None,
+            access_ttl_minutes: 15,
+            refresh_ttl_days: 30,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WechatProvider {
+    /// 调用微信官方接口
+    #[default]
+    Official,
+    /// 本地开发用的假实现，不访问网络
+    Fake,
+}
+
+impl FromStr for WechatProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "official" => Ok(Self::Official),
+            "fake" => Ok(Self::Fake),
+            _ => anyhow::bail!("unknown wechat provider `{s}`"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WechatSettings {
+    pub provider: WechatProvider,
+    pub base_url: String,
+    pub appid: String,
+    pub secret: Secret,
+    pub timeout_secs: u64,
+    pub retries: u32,
+}
+
+impl Default for WechatSettings {
+    fn default() -> Self {
+        Self {
+            provider: WechatProvider::Official,
+            base_url: DEFAULT_WECHAT_BASE_URL.to_string(),
+            appid: String::new(),
+            secret: Secret::default(),
+            timeout_secs: 5,
+            retries: 2,
+        }
+    }
+}
+
+/// 上传图片的处理配置
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ImageSettings {
+    /// 需要生成的尺寸，名称会出现在对象 key 和接口返回中
+    pub variants: Vec<ImageVariantSettings>,
+    /// 原尺寸输出这些格式，缩小后的尺寸只输出 JPEG
+    pub formats: Vec<OutputFormat>,
+    pub jpeg_quality: u8,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ImageVariantSettings {
+    pub name: String,
+    /// 最长边的像素上限，未设置时保持原图尺寸；不会放大比上限小的图片
+    pub max_size: Option<u32>,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        let variant = |name: &str, max_size| ImageVariantSettings {
+            name: name.to_string(),
+            max_size,
+        };
+        Self {
+            variants: vec![
+                variant("thumb", Some(320)),
+                variant("medium", Some(1080)),
+                variant("original", None),
+            ],
+            formats: vec![OutputFormat::Webp, OutputFormat::Jpeg],
+            jpeg_quality: 85,
+        }
+    }
+}
+
+/// S3 分片上传中除最后一片外的最小分片大小
+const MIN_PART_BYTES: usize = 5 * 1024 * 1024;
+
+/// 上传限制
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct UploadSettings {
+    /// 单个文件的最大字节数
+    pub max_file_bytes: usize,
+    /// 单次请求最多上传的文件数
+    pub max_files: usize,
+    /// 允许的图片类型，按文件内容识别
+    pub allowed_types: Vec<String>,
+    /// 解码后的最大像素数（宽 x 高），用于拒绝解压炸弹
+    pub max_pixels: u64,
+    /// 每个用户默认的存储配额，单位字节
+    pub quota_bytes: i64,
+    /// 允许通过分片上传的视频类型，按文件头识别
+    pub video_types: Vec<String>,
+    /// 单个视频的最大字节数
+    pub max_video_bytes: u64,
+    /// 分片大小，S3 要求除最后一片外不小于 5MB
+    pub part_bytes: usize,
+    /// 分片上传会话的有效期，超时未完成的会话由清理任务放弃，单位秒
+    pub session_ttl_secs: u64,
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 9,
+            allowed_types: ["image/jpeg", "image/png", "image/webp", "image/gif"]
+                .map(String::from)
+                .to_vec(),
+            max_pixels: 40_000_000,
+            quota_bytes: 1024 * 1024 * 1024,
+            video_types: ["video/mp4", "video/quicktime"].map(String::from).to_vec(),
+            max_video_bytes: 200 * 1024 * 1024,
+            part_bytes: 5 * 1024 * 1024,
+            session_ttl_secs: 24 * 3600,
+        }
+    }
+}
+
+impl UploadSettings {
+    /// multipart 请求体上限：全部文件加上表单本身的开销
+    pub fn max_request_bytes(&self) -> usize {
+        self.max_file_bytes.saturating_mul(self.max_files) + 64 * 1024
+    }
+}
+
+/// 孤儿图片、过期上传会话和回收站的定期清理任务
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GcSettings {
+    /// 是否在服务内定期执行，关闭后仍可通过命令行手动执行
+    pub enabled: bool,
+    /// 执行间隔，单位秒
+    pub interval_secs: u64,
+    /// 上传后超过这段时间仍未被任何 block 引用的图片才会被删除，单位秒
+    pub grace_period_secs: u64,
+    /// block 在回收站中保留的时间，超过后连同其图片永久删除，单位秒
+    pub trash_retention_secs: u64,
+}
+
+impl Default for GcSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 3600,
+            grace_period_secs: 24 * 3600,
+            trash_retention_secs: 30 * 24 * 3600,
+        }
+    }
+}
+
+/// 定时发布任务
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PublisherSettings {
+    /// 检查到期 block 的间隔，单位秒
+    pub interval_secs: u64,
+}
+
+impl Default for PublisherSettings {
+    fn default() -> Self {
+        Self { interval_secs: 60 }
+    }
+}
+
+impl AppConfig {
+    /// 从配置文件和进程环境变量加载配置
+    /// 配置文件路径取自 APP_CONFIG，默认当前目录下的 `config.toml`，文件不存在时跳过
+    pub fn load() -> anyhow::Result<Self> {
+        let path = env::var("APP_CONFIG").unwrap_or_else(|_| "config.toml".to_string());
+        Self::load_from(Path::new(&path), |name| env::var(name).ok())
+    }
+
+    /// `var` 用于读取环境变量，测试中可以传入自定义的查找函数
+    pub fn load_from(path: &Path, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let profile = var("APP_ENV").unwrap_or_else(|| "development".to_string());
+
+        let mut table = toml::Table::new();
+        merge(&mut table, read_table(path)?);
+        merge(&mut table, read_table(&profile_path(path, &profile))?);
+
+        let mut config: AppConfig = table.try_into()?;
+        config.env = profile;
+        config.apply_env(&var)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.env == "production"
+    }
+
+    pub fn is_development(&self) -> bool {
+        self.env == "development"
+    }
+
+    /// 环境变量覆盖配置文件，变量名与旧版 .env 保持一致
+    fn apply_env(&mut self, var: &impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        override_with(var, "HOST", &mut self.server.host)?;
+        override_with(var, "PORT", &mut self.server.port)?;
+        override_with(var, "DATABASE_URL", &mut self.database.url)?;
+
+        override_with(var, "STORAGE_BACKEND", &mut self.storage.backend)?;
+        override_with(var, "STORAGE_LOCAL_ROOT", &mut self.storage.local.root)?;
+        if let Some(url) = var("STORAGE_LOCAL_PUBLIC_BASE_URL") {
+            self.storage.local.public_base_url = Some(url);
+        }
+
+        override_with(var, "MINIO_ENDPOINT", &mut self.minio.endpoint)?;
+        override_with(var, "MINIO_ACCESS_KEY", &mut self.minio.access_key)?;
+        override_with(var, "MINIO_SECRET_KEY", &mut self.minio.secret_key)?;
+        override_with(var, "MINIO_BUCKET", &mut self.minio.bucket)?;
+        if let Some(url) = var("MINIO_PUBLIC_BASE_URL") {
+            self.minio.public_base_url = Some(url);
+        }
+        override_with(
+            var,
+            "MINIO_PRESIGN_EXPIRY_SECS",
+            &mut self.minio.presign_expiry_secs,
+        )?;
+
+        // JWT_KEYS 形如 `kid1:secret1,kid2:secret2`；只配置了 JWT_SECRET 时 kid 为 `default`
+        if let Some(keys) = var("JWT_KEYS") {
+            self.jwt.keys.clear();
+            for pair in keys.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (kid, secret) = pair
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("JWT_KEYS entry must be `kid:secret`"))?;
+                self.jwt.active_kid.get_or_insert_with(|| kid.to_string());
+                self.jwt.keys.insert(kid.to_string(), Secret::new(secret));
+            }
+        } else if let Some(secret) = var("JWT_SECRET") {
+            if self.jwt.keys.is_empty() {
+                self.jwt
+                    .keys
+                    .insert("default".to_string(), Secret::new(secret));
+            }
+        }
+        if let Some(kid) = var("JWT_ACTIVE_KID") {
+            self.jwt.active_kid = Some(kid);
+        }
+        override_with(
+            var,
+            "JWT_ACCESS_TTL_MINUTES",
+            &mut self.jwt.access_ttl_minutes,
+        )?;
+        override_with(var, "JWT_REFRESH_TTL_DAYS", &mut self.jwt.refresh_ttl_days)?;
+
+        override_with(var, "WECHAT_PROVIDER", &mut self.wechat.provider)?;
+        override_with(var, "WECHAT_BASE_URL", &mut self.wechat.base_url)?;
+        override_with(var, "WECHAT_APPID", &mut self.wechat.appid)?;
+        override_with(var, "WECHAT_SECRET", &mut self.wechat.secret)?;
+        override_with(var, "WECHAT_TIMEOUT_SECS", &mut self.wechat.timeout_secs)?;
+        override_with(var, "WECHAT_RETRIES", &mut self.wechat.retries)?;
+
+        override_with(var, "GC_ENABLED", &mut self.gc.enabled)?;
+        override_with(var, "GC_INTERVAL_SECS", &mut self.gc.interval_secs)?;
+        override_with(var, "GC_GRACE_PERIOD_SECS", &mut self.gc.grace_period_secs)?;
+        override_with(
+            var,
+            "GC_TRASH_RETENTION_SECS",
+            &mut self.gc.trash_retention_secs,
+        )?;
+        override_with(
+            var,
+            "PUBLISHER_INTERVAL_SECS",
+            &mut self.publisher.interval_secs,
+        )?;
+        Ok(())
+    }
+
+    /// 启动前校验配置，缺失或不合法时给出明确的错误信息
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.database.url.is_empty() {
+            anyhow::bail!("database.url (DATABASE_URL) is not set");
+        }
+        match self.storage.backend {
+            StorageBackend::Minio => {
+                self.minio
+                    .endpoint
+                    .parse::<BaseUrl>()
+                    .map_err(|e| anyhow::anyhow!("minio.endpoint is invalid: {e}"))?;
+                if self.minio.bucket.is_empty() {
+                    anyhow::bail!("minio.bucket (MINIO_BUCKET) is not set");
+                }
+                if self.minio.access_key.is_empty() || self.minio.secret_key.is_empty() {
+                    anyhow::bail!("minio.access_key and minio.secret_key (MINIO_ACCESS_KEY / MINIO_SECRET_KEY) must be set");
+                }
+            }
+            StorageBackend::Local => {
+                if self.storage.local.root.is_empty() {
+                    anyhow::bail!("storage.local.root (STORAGE_LOCAL_ROOT) is not set");
+                }
+            }
+        }
+        // MinIO 默认账号只允许在本地开发时使用
+        if !self.is_development()
+            && (self.minio.access_key == DEFAULT_MINIO_CREDENTIAL
+                || self.minio.secret_key.expose() == DEFAULT_MINIO_CREDENTIAL)
+        {
+            anyhow::bail!("the default minio credentials cannot be used outside development");
+        }
+        // S3 预签名 URL 最长有效 7 天
+        if !(1..=604_800).contains(&self.minio.presign_expiry_secs) {
+            anyhow::bail!("minio.presign_expiry_secs must be between 1 and 604800");
+        }
+
+        if self.jwt.keys.is_empty() {
+            anyhow::bail!("jwt.keys (JWT_KEYS or JWT_SECRET) is not set");
+        }
+        if self.jwt.keys.values().any(Secret::is_empty) {
+            anyhow::bail!("jwt.keys must not contain empty secrets");
+        }
+        match &self.jwt.active_kid {
+            Some(kid) if !self.jwt.keys.contains_key(kid) => {
+                anyhow::bail!("jwt.active_kid `{kid}` is not present in jwt.keys")
+            }
+            None if self.jwt.keys.len() > 1 => {
+                anyhow::bail!("jwt.active_kid must be set when several jwt.keys are configured")
+            }
+            _ => {}
+        }
+        if self.jwt.access_ttl_minutes <= 0 || self.jwt.refresh_ttl_days <= 0 {
+            anyhow::bail!("jwt token ttl must be positive");
+        }
+
+        match self.wechat.provider {
+            WechatProvider::Official => {
+                if self.wechat.appid.is_empty() || self.wechat.secret.is_empty() {
+                    anyhow::bail!(
+                        "wechat.appid and wechat.secret (WECHAT_APPID / WECHAT_SECRET) must be set"
+                    );
+                }
+            }
+            WechatProvider::Fake if self.is_production() => {
+                anyhow::bail!("the fake wechat provider cannot be used in production");
+            }
+            WechatProvider::Fake => {}
+        }
+
+        if self.image.variants.is_empty() || self.image.formats.is_empty() {
+            anyhow::bail!("image.variants and image.formats must not be empty");
+        }
+        for (i, variant) in self.image.variants.iter().enumerate() {
+            let valid_name = !variant.name.is_empty()
+                && variant
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid_name {
+                anyhow::bail!(
+                    "image variant name `{}` must be ascii letters, digits, `_` or `-`",
+                    variant.name
+                );
+            }
+            if self.image.variants[..i]
+                .iter()
+                .any(|v| v.name == variant.name)
+            {
+                anyhow::bail!("image variant `{}` is configured twice", variant.name);
+            }
+            if variant.max_size == Some(0) {
+                anyhow::bail!("image variant `{}` max_size must be positive", variant.name);
+            }
+        }
+        if !(1..=100).contains(&self.image.jpeg_quality) {
+            anyhow::bail!("image.jpeg_quality must be between 1 and 100");
+        }
+
+        if self.upload.max_file_bytes == 0
+            || self.upload.max_files == 0
+            || self.upload.max_pixels == 0
+        {
+            anyhow::bail!(
+                "upload.max_file_bytes, upload.max_files and upload.max_pixels must be positive"
+            );
+        }
+        if self.upload.allowed_types.is_empty() {
+            anyhow::bail!("upload.allowed_types must not be empty");
+        }
+        if self.upload.quota_bytes < 0 {
+            anyhow::bail!("upload.quota_bytes must not be negative");
+        }
+        if self.upload.part_bytes < MIN_PART_BYTES {
+            anyhow::bail!("upload.part_bytes must be at least {MIN_PART_BYTES}");
+        }
+        // S3 一次分片上传最多 10000 个分片
+        let largest = self
+            .upload
+            .max_video_bytes
+            .max(self.upload.max_file_bytes as u64);
+        if largest.div_ceil(self.upload.part_bytes as u64) > 10_000 {
+            anyhow::bail!("upload.part_bytes is too small for the maximum upload size");
+        }
+        if self.upload.session_ttl_secs == 0 {
+            anyhow::bail!("upload.session_ttl_secs must be positive");
+        }
+
+        if self.gc.interval_secs == 0 {
+            anyhow::bail!("gc.interval_secs must be positive");
+        }
+        if self.publisher.interval_secs == 0 {
+            anyhow::bail!("publisher.interval_secs must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// `config.toml` 对应的环境配置文件为 `config.{profile}.toml`
+fn profile_path(path: &Path, profile: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("config");
+    path.with_file_name(format!("{stem}.{profile}.toml"))
+}
+
+fn read_table(path: &Path) -> anyhow::Result<toml::Table> {
+    match fs::read_to_string(path) {
+        Ok(content) => content
+            .parse()
+            .map_err(|e| anyhow::anyhow!("failed to parse {}: {e}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(anyhow::anyhow!("failed to read {}: {e}", path.display())),
+    }
+}
+
+/// 递归合并配置表，overlay 中的值覆盖 base
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn override_with<T>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    target: &mut T,
+) -> anyhow::Result<()>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(value) = var(name) {
+        *target = value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{name} is invalid: {e}"))?;
+    }
+    Ok(())
+}
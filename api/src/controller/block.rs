@@ -1,39 +1,150 @@
-use crate::tools::{AppState, Params, ResponseData, ResponseStatus};
+use crate::{
+    error::ApiError,
+    extract::{etag, IfMatch, ValidatedJson, ValidatedQuery},
+    gc,
+    tools::{AppState, ResponseData, ResponseStatus},
+};
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Path, State},
+    response::{IntoResponse, Json},
     Extension,
 };
 use entity::users::Model as UserEntity;
-use futures_util::StreamExt;
-use minio::s3::{builders::ObjectContent, types::S3Api};
-use service::{block::BlockModel, sea_orm::sqlx::types::uuid, BlockServices};
+use service::{
+    block::{AutosaveModel, BlockModel, BlockPatch, BlockQuery, BlockStatus, TimelineQuery},
+    pagination::{Cursor, SortOrder},
+    revision::{diff, BlockContent},
+    sea_orm::prelude::DateTimeWithTimeZone,
+    sea_orm::sqlx::types::uuid,
+    validation::validate_search_query,
+    BlockServices, RevisionServices, SearchHistoryServices,
+};
 
+use serde::Deserialize;
 use serde_json::json;
 use serde_json::to_value;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 pub struct BlockController;
 
+/// 附近查询的最大半径，单位米
+const MAX_NEARBY_RADIUS_M: f64 = 50_000.0;
+
+#[derive(Deserialize, Validate)]
+pub struct SearchParams {
+    #[validate(custom(function = "validate_search_query"))]
+    pub q: String,
+    #[validate(range(min = 1, message = "page starts from 1"))]
+    pub page: Option<u64>,
+    #[validate(range(min = 1, max = 50, message = "posts_per_page must be between 1 and 50"))]
+    pub posts_per_page: Option<u64>,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct NearbyParams {
+    #[validate(range(min = -90.0, max = 90.0, message = "lat must be between -90 and 90"))]
+    pub lat: f64,
+    #[validate(range(min = -180.0, max = 180.0, message = "lon must be between -180 and 180"))]
+    pub lon: f64,
+    /// 查询半径，单位米，默认 1000
+    #[validate(range(exclusive_min = 0.0, max = MAX_NEARBY_RADIUS_M, message = "radius must be between 0 and 50000 meters"))]
+    pub radius: Option<f64>,
+    #[validate(range(min = 1, max = 100, message = "limit must be between 1 and 100"))]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct BlockListParams {
+    #[validate(range(min = 1, message = "page starts from 1"))]
+    pub page: Option<u64>,
+    #[validate(range(min = 1, max = 50, message = "posts_per_page must be between 1 and 50"))]
+    pub posts_per_page: Option<u64>,
+    /// 上一次返回的 next_cursor，提供时忽略 page
+    pub cursor: Option<Cursor>,
+    /// 按创建时间排序的方向，默认 asc
+    pub order: Option<SortOrder>,
+    /// 只列出指定用户的 block
+    pub owner: Option<uuid::Uuid>,
+    /// 只列出指定状态的 block，省略时列出全部可见的 block
+    pub status: Option<BlockStatus>,
+    pub from: Option<DateTimeWithTimeZone>,
+    pub to: Option<DateTimeWithTimeZone>,
+    pub has_images: Option<bool>,
+    #[validate(length(min = 1, max = 200, message = "location must be 1 to 200 characters"))]
+    pub location: Option<String>,
+}
+
+impl BlockListParams {
+    pub fn query(&self) -> BlockQuery {
+        BlockQuery {
+            owner: self.owner,
+            status: self.status,
+            from: self.from,
+            to: self.to,
+            has_images: self.has_images,
+            location: self.location.clone(),
+            order: self.order.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct DiffParams {
+    #[validate(range(min = 1, message = "from must be a revision number"))]
+    pub from: i32,
+    /// 省略时与 block 当前内容比较
+    #[validate(range(min = 1, message = "to must be a revision number"))]
+    pub to: Option<i32>,
+}
+
+/// block 只能引用当前用户通过 upload_pic 上传到本站存储的图片
+fn ensure_own_images(
+    state: &AppState,
+    user: &UserEntity,
+    imgs: &Option<Vec<String>>,
+) -> Result<(), ApiError> {
+    // 图片和分片上传的视频都只能引用自己上传的文件
+    let prefixes =
+        ["images", "videos"].map(|kind| state.store.public_url(&format!("{}/{}/", kind, user.id)));
+    let foreign = imgs
+        .iter()
+        .flatten()
+        .any(|url| !prefixes.iter().any(|prefix| url.starts_with(prefix)));
+    if foreign {
+        let mut errors = ValidationErrors::new();
+        errors.add(
+            "imgs",
+            ValidationError::new("foreign_image")
+                .with_message("images must be uploaded through /api/upload_pic".into()),
+        );
+        return Err(errors.into());
+    }
+    Ok(())
+}
+
 impl BlockController {
     pub async fn block_list(
+        Extension(user): Extension<UserEntity>,
         state: State<AppState>,
-        Query(params): Query<Params>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
+        ValidatedQuery(params): ValidatedQuery<BlockListParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
         let page = params.page.unwrap_or(1);
         let posts_per_page = params.posts_per_page.unwrap_or(5);
 
-        let (blocks, num_pages) = BlockServices::find_blocks(&state.conn, page, posts_per_page)
-            .await
-            .expect("Cannot find blocks in page");
+        let blocks = BlockServices::find_blocks(
+            &state.conn,
+            user.id,
+            &params.query(),
+            params.cursor,
+            page,
+            posts_per_page,
+        )
+        .await?;
 
         let data = ResponseData {
             code: 200,
             status: ResponseStatus::Success,
-            data: Some(json!({
-                "rows": blocks,
-                "num_pages": num_pages,
-            })),
+            data: Some(json!(blocks)),
             message: Some("Blocks retrieved successfully".to_string()),
         };
 
@@ -42,157 +153,122 @@ impl BlockController {
         Ok(Json(json!(json_data)))
     }
 
-    pub async fn upload_pic(
+    /// 当前用户自己的 block，包括草稿，参数与 block_list 相同，owner 被忽略
+    pub async fn my_blocks(
         Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
-        mut multipart: Multipart,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        let mut image_url: Option<Vec<String>> = None;
-
-        loop {
-            match multipart.next_field().await {
-                Ok(Some(field)) => {
-                    let name = field.name().map(|s| s.to_string()).unwrap_or_default();
-
-                    if name == "image" {
-                        // 處理圖片上傳
-                        if let Some(filename) = field.file_name().map(|f| f.to_string()) {
-                            // Read the field as a stream and collect bytes
-                            let mut data_bytes: Vec<u8> = Vec::new();
-                            let mut stream = field;
-                            while let Some(chunk_res) = stream.next().await {
-                                match chunk_res {
-                                    Ok(chunk) => data_bytes.extend_from_slice(&chunk),
-                                    Err(e) => {
-                                        eprintln!("failed to read file chunk: {:?}", e);
-                                        return Err((
-                                            StatusCode::BAD_REQUEST,
-                                            "failed to read file bytes",
-                                        ));
-                                    }
-                                }
-                            }
-
-                            let object_key = format!("images/{}/{}", user.id, filename);
-                            let content = ObjectContent::from(data_bytes.clone());
-                            // 上傳到 MinIO
-                            let res = state
-                                .client
-                                .put_object_content(&state.bucket, &object_key, content)
-                                .send()
-                                .await;
-
-                            match res {
-                                Ok(_) => {
-
-                                    // 拼圖片訪問 URL
-                                    let url = format!("{}/{}", state.base_url, object_key);
-                                    image_url.get_or_insert_with(Vec::new).push(url);
-                                }
-                                Err(e) => {
-                                    eprintln!("上傳圖片失敗: {:?}", e);
-                                    return Err((
-                                        StatusCode::INTERNAL_SERVER_ERROR,
-                                        "upload image failed",
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                }
-                Ok(None) => break,
-                Err(e) => {
-                    eprintln!("multipart read error: {:?}", e);
-                    return Err((StatusCode::BAD_REQUEST, "failed to read multipart stream"));
-                }
-            }
-        }
+        ValidatedQuery(params): ValidatedQuery<BlockListParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let page = params.page.unwrap_or(1);
+        let posts_per_page = params.posts_per_page.unwrap_or(5);
+        let query = BlockQuery {
+            owner: None,
+            ..params.query()
+        };
+
+        let blocks = BlockServices::find_blocks_by_pid(
+            &state.conn,
+            user.id,
+            &query,
+            params.cursor,
+            page,
+            posts_per_page,
+        )
+        .await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!(blocks)),
+            message: Some("Blocks retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 当前用户的 block 按天或按月汇总的数量，用于日历视图
+    pub async fn my_timeline(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        ValidatedQuery(params): ValidatedQuery<TimelineQuery>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let buckets = BlockServices::timeline(&state.conn, user.id, &params).await?;
 
         let data = ResponseData {
             code: 200,
             status: ResponseStatus::Success,
             data: Some(json!({
-                "image_url": image_url,
+                "granularity": params.granularity,
+                "rows": buckets,
             })),
-            message: Some("Image uploaded successfully".to_string()),
+            message: Some("Timeline retrieved successfully".to_string()),
         };
+        Ok(Json(json!(data)))
+    }
 
-        let json_data = to_value(data).unwrap();
-        println!("Json data: {:?}", json_data);
-        Ok(Json(json!(json_data)))
+    pub async fn nearby_blocks(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        ValidatedQuery(params): ValidatedQuery<NearbyParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let radius = params.radius.unwrap_or(1000.0);
+        let limit = params.limit.unwrap_or(20);
+
+        let blocks = BlockServices::find_blocks_near(
+            &state.conn,
+            user.id,
+            params.lat,
+            params.lon,
+            radius,
+            limit,
+        )
+        .await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "rows": blocks,
+            })),
+            message: Some("Nearby blocks retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
     }
 
-    pub async fn delete_pic(
+    pub async fn search_blocks(
         Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
-        mut multipart: Multipart,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        let mut image_url: Option<Vec<String>> = None;
-        loop {
-            match multipart.next_field().await {
-                Ok(Some(field)) => {
-                    let name = field.name().map(|s| s.to_string()).unwrap_or_default();
-
-                    if name == "image" {
-                        // 處理圖片刪除
-                        if let Some(filename) = field.file_name() {
-                            let object_key = format!("images/{}/{}", user.id, filename);
-
-                            // 從 MinIO 刪除圖片
-                            let res = state
-                                .client
-                                .delete_object(&state.bucket, &object_key)
-                                .send()
-                                .await;
-
-                            match res {
-                                Ok(_) => {
-                                    // 拼圖片訪問 URL
-                                    let url = format!("{}/{}", state.base_url, object_key);
-                                    image_url.get_or_insert_with(Vec::new).push(url);
-                                }
-                                Err(e) => {
-                                    eprintln!("刪除圖片失敗: {:?}", e);
-                                    return Err((
-                                        StatusCode::INTERNAL_SERVER_ERROR,
-                                        "delete image failed",
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                }
-                Ok(None) => break,
-                Err(e) => {
-                    eprintln!("multipart read error: {:?}", e);
-                    return Err((StatusCode::BAD_REQUEST, "failed to read multipart stream"));
-                }
-            }
+        ValidatedQuery(params): ValidatedQuery<SearchParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let query = params.q.trim();
+        let page = params.page.unwrap_or(1);
+        let posts_per_page = params.posts_per_page.unwrap_or(10);
+
+        let hits =
+            BlockServices::search_blocks(&state.conn, user.id, query, page, posts_per_page).await?;
+
+        // 搜索历史记录失败不影响搜索结果
+        if let Err(e) = SearchHistoryServices::record_query(&state.conn, user.id, query).await {
+            tracing::warn!("failed to record search history: {:?}", e);
         }
+
         let data = ResponseData {
             code: 200,
             status: ResponseStatus::Success,
             data: Some(json!({
-                "image_url": image_url,
+                "rows": hits,
             })),
-            message: Some("Image deleted successfully".to_string()),
+            message: Some("Blocks searched successfully".to_string()),
         };
-        let json_data = to_value(data).unwrap();
-        println!("Json data: {:?}", json_data);
-        Ok(Json(json!(json_data)))
+        Ok(Json(json!(data)))
     }
 
     pub async fn create_block(
         Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
-        Json(payload): Json<BlockModel>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        BlockServices::create_block(&state.conn, payload, user.id)
-            .await
-            .map_err(|e| {
-                println!("Failed to create block: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create block")
-            })?;
+        ValidatedJson(payload): ValidatedJson<BlockModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        ensure_own_images(&state, &user, &payload.imgs)?;
+        BlockServices::create_block(&state.conn, payload, user.id).await?;
 
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 201,
@@ -204,15 +280,11 @@ impl BlockController {
     }
 
     pub async fn get_block(
+        Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
         Path(id): Path<uuid::Uuid>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        let block = BlockServices::get_block_by_id(&state.conn, id)
-            .await
-            .map_err(|e| {
-                println!("Failed to get block: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get block")
-            })?;
+    ) -> Result<impl IntoResponse, ApiError> {
+        let block = BlockServices::get_visible_block(&state.conn, id, user.id).await?;
         let data = ResponseData {
             code: 200,
             status: ResponseStatus::Success,
@@ -221,20 +293,17 @@ impl BlockController {
             })),
             message: Some("Block retrieved successfully".to_string()),
         };
-        Ok(Json(json!(data)))
+        Ok((etag(&block.update_time), Json(json!(data))))
     }
 
     pub async fn update_block(
+        Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
         Path(id): Path<uuid::Uuid>,
-        Json(payload): Json<BlockModel>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        BlockServices::update_block_by_id(&state.conn, id, payload)
-            .await
-            .map_err(|e| {
-                println!("Failed to update block: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update block")
-            })?;
+        ValidatedJson(payload): ValidatedJson<BlockModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        ensure_own_images(&state, &user, &payload.imgs)?;
+        BlockServices::update_block_for_owner(&state.conn, id, user.id, payload).await?;
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 200,
             status: ResponseStatus::Success,
@@ -244,21 +313,216 @@ impl BlockController {
         Ok(Json(json!(data)))
     }
 
+    /// 部分修改 block，只修改提交的字段；带 If-Match 时版本不一致返回 412
+    pub async fn patch_block(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+        IfMatch(expected): IfMatch,
+        ValidatedJson(payload): ValidatedJson<BlockPatch>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        ensure_own_images(&state, &user, &payload.imgs.value().cloned())?;
+        let block =
+            BlockServices::patch_block_for_owner(&state.conn, id, user.id, expected, payload)
+                .await?;
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "block": block,
+            })),
+            message: Some("Block updated successfully".to_string()),
+        };
+        Ok((etag(&block.update_time), Json(json!(data))))
+    }
+
+    /// 自动保存草稿，只有作者可以保存，不会产生历史版本
+    pub async fn autosave_block(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+        ValidatedJson(payload): ValidatedJson<AutosaveModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        ensure_own_images(&state, &user, &payload.imgs)?;
+        let block = BlockServices::autosave_for_owner(&state.conn, id, user.id, payload).await?;
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "block": block,
+            })),
+            message: Some("Draft saved".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
     pub async fn delete_block(
+        Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
         Path(id): Path<uuid::Uuid>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        BlockServices::delete_block_by_id(&state.conn, id)
-            .await
-            .map_err(|e| {
-                println!("Failed to delete block: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete block")
-            })?;
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        BlockServices::delete_block_for_owner(&state.conn, id, user.id).await?;
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 200,
             status: ResponseStatus::Success,
             data: None,
-            message: Some("Block deleted successfully".to_string()),
+            message: Some("Block moved to trash".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 列出 block 的历史版本，只有作者可以查看
+    pub async fn list_revisions(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let block = BlockServices::get_owned_block(&state.conn, id, user.id).await?;
+        let revisions = RevisionServices::list_revisions(&state.conn, block.id).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "rows": revisions,
+            })),
+            message: Some("Revisions retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    pub async fn get_revision(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path((id, revision)): Path<(uuid::Uuid, i32)>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let block = BlockServices::get_owned_block(&state.conn, id, user.id).await?;
+        let revision = RevisionServices::get_revision(&state.conn, block.id, revision).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "revision": revision,
+            })),
+            message: Some("Revision retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 比较两个历史版本，或某个历史版本与当前内容
+    pub async fn diff_revisions(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+        ValidatedQuery(params): ValidatedQuery<DiffParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let block = BlockServices::get_owned_block(&state.conn, id, user.id).await?;
+        let from = RevisionServices::get_revision(&state.conn, block.id, params.from).await?;
+        let to = match params.to {
+            Some(to) => BlockContent::from(
+                &RevisionServices::get_revision(&state.conn, block.id, to).await?,
+            ),
+            None => BlockContent::from(&block),
+        };
+        let diff = diff(&BlockContent::from(&from), &to);
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "from": params.from,
+                "to": params.to,
+                "changes": diff.changes,
+                "context": diff.context,
+            })),
+            message: Some("Revisions compared successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 恢复到某个历史版本，恢复前的内容会保存为新的版本
+    pub async fn restore_revision(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path((id, revision)): Path<(uuid::Uuid, i32)>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let block =
+            BlockServices::restore_revision_for_owner(&state.conn, id, user.id, revision).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "block": block,
+            })),
+            message: Some("Block restored successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 回收站中的 block，purge_at 为到期后被自动永久删除的时间
+    pub async fn trash_list(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let blocks = BlockServices::find_trashed_blocks(&state.conn, user.id).await?;
+        let retention = chrono::Duration::seconds(state.config.gc.trash_retention_secs as i64);
+        let rows: Vec<serde_json::Value> = blocks
+            .into_iter()
+            .map(|block| {
+                let purge_at = block.deleted_at.map(|deleted_at| deleted_at + retention);
+                let mut row = json!(block);
+                row["purge_at"] = json!(purge_at);
+                row
+            })
+            .collect();
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "rows": rows,
+            })),
+            message: Some("Trash retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    pub async fn restore_block(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let block = BlockServices::restore_block_for_owner(&state.conn, id, user.id).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "block": block,
+            })),
+            message: Some("Block restored successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 永久删除回收站中的 block，其中不再被其他 block 引用的图片一并删除
+    pub async fn purge_block(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let block = BlockServices::get_trashed_block(&state.conn, id, user.id).await?;
+        let media_removed = gc::purge_blocks(&state.conn, state.store.as_ref(), &[block]).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "media_removed": media_removed,
+            })),
+            message: Some("Block deleted permanently".to_string()),
         };
         Ok(Json(json!(data)))
     }
@@ -0,0 +1,443 @@
+use crate::{
+    error::ApiError,
+    extract::{ValidatedJson, ValidatedQuery},
+    imaging::{self, OutputFormat},
+    object_store::StorageError,
+    tools::{AppState, ResponseData, ResponseStatus},
+};
+use axum::{
+    extract::{Multipart, Path, State},
+    response::Json,
+    Extension,
+};
+use entity::{media::Model as MediaEntity, users::Model as UserEntity};
+use serde::Deserialize;
+use service::{
+    media::{variants_of, MediaVariant, NewMedia},
+    sea_orm::sqlx::types::uuid,
+    BlockServices, MediaServices, StorageServices,
+};
+use sha2::{Digest, Sha256};
+use validator::Validate;
+
+use serde_json::json;
+
+pub struct MediaController;
+
+#[derive(Deserialize, Validate)]
+pub struct PresignRequest {
+    /// 客户端上传时必须使用的 Content-Type
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "content_type must be 1 to 100 characters"
+    ))]
+    pub content_type: String,
+    /// 文件大小，单位字节
+    #[validate(range(min = 1, message = "size must be positive"))]
+    pub size: u64,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct ConfirmRequest {
+    pub upload_id: uuid::Uuid,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct MediaUrlParams {
+    /// 尺寸名称，默认原图
+    #[validate(length(min = 1, max = 32, message = "variant must be 1 to 32 characters"))]
+    pub variant: Option<String>,
+    /// 格式，默认配置中的首选格式
+    pub format: Option<OutputFormat>,
+}
+
+/// 图片的接口表示：各尺寸的宽高和各格式 URL，url 为原图地址
+pub(crate) fn media_json(state: &AppState, media: &MediaEntity) -> serde_json::Value {
+    let mut sizes = serde_json::Map::new();
+    for variant in variants_of(media) {
+        let size = sizes.entry(variant.name).or_insert_with(
+            || json!({ "width": variant.width, "height": variant.height, "urls": {} }),
+        );
+        size["urls"][variant.format] = json!(state.store.public_url(&variant.key));
+    }
+    json!({
+        "id": media.id,
+        "url": state.store.public_url(&media.object_key),
+        "mime": media.mime,
+        "width": media.width,
+        "height": media.height,
+        "size": media.size,
+        "variants": sizes,
+    })
+}
+
+/// 删除图片的全部尺寸，单个对象删除失败只记录日志，留给孤儿文件清理
+pub(crate) async fn remove_objects(state: &AppState, keys: impl IntoIterator<Item = String>) {
+    for key in keys {
+        if let Err(e) = state.store.delete(&key).await {
+            tracing::warn!("failed to delete object {}: {:?}", key, e);
+        }
+    }
+}
+
+/// 处理并保存一张新图片：生成各尺寸、预占配额、写入对象存储和 media 记录，任一步失败都会回滚配额
+async fn store_media(
+    state: &AppState,
+    owner: uuid::Uuid,
+    content_hash: String,
+    data_bytes: Vec<u8>,
+) -> Result<MediaEntity, ApiError> {
+    // 识别类型、解码、去除 EXIF 并生成各尺寸，CPU 密集放到阻塞线程池
+    let settings = state.config.image.clone();
+    let limits = state.config.upload.clone();
+    let processed =
+        tokio::task::spawn_blocking(move || imaging::process(&data_bytes, &settings, &limits))
+            .await
+            .map_err(|e| ApiError::Internal(e.to_string()))??;
+
+    let id = uuid::Uuid::new_v4();
+    let variants: Vec<MediaVariant> = processed
+        .variants
+        .iter()
+        .map(|variant| MediaVariant {
+            name: variant.name.clone(),
+            format: variant.format.as_str().to_string(),
+            key: imaging::object_key(owner, id, &variant.name, variant.format),
+            width: variant.width,
+            height: variant.height,
+            bytes: variant.bytes.len() as i64,
+        })
+        .collect();
+    // 原图取最后一个尺寸（默认为 original）的首选格式
+    let primary = state
+        .config
+        .image
+        .variants
+        .last()
+        .map(|v| v.name.as_str())
+        .unwrap_or_default();
+    let object_key = variants
+        .iter()
+        .find(|v| v.name == primary)
+        .unwrap_or(&variants[0])
+        .key
+        .clone();
+    let new = NewMedia {
+        id,
+        owner,
+        object_key,
+        content_hash,
+        mime: processed.mime.to_string(),
+        width: processed.width,
+        height: processed.height,
+        variants,
+    };
+
+    // 按实际写入存储的字节数计入配额
+    let size = new.size();
+    let objects = new.variants.len() as i32;
+    StorageServices::reserve(
+        &state.conn,
+        owner,
+        size,
+        objects,
+        state.config.upload.quota_bytes,
+    )
+    .await?;
+
+    let mut stored = Vec::new();
+    let mut result = Ok(());
+    for (variant, encoded) in new.variants.iter().zip(processed.variants) {
+        // 写入对象存储
+        result = state
+            .store
+            .put(&variant.key, encoded.bytes, encoded.format.content_type())
+            .await;
+        if result.is_err() {
+            break;
+        }
+        stored.push(variant.key.clone());
+    }
+    let content_hash = new.content_hash.clone();
+    let result = match result {
+        Ok(()) => MediaServices::create_media(&state.conn, new)
+            .await
+            .map_err(ApiError::from),
+        Err(e) => Err(e.into()),
+    };
+
+    if !matches!(result, Ok(Some(_))) {
+        remove_objects(state, stored).await;
+        if let Err(e) = StorageServices::release(&state.conn, owner, size, objects).await {
+            tracing::warn!("failed to release storage quota: {:?}", e);
+        }
+    }
+    match result? {
+        Some(media) => Ok(media),
+        // 同一用户并发上传了相同内容，返回先写入的记录，本次写入的对象和配额已回滚
+        None => MediaServices::find_by_hash(&state.conn, owner, &content_hash)
+            .await?
+            .ok_or_else(|| ApiError::Internal("conflicting media record not found".to_string())),
+    }
+}
+
+/// 保存上传的原始图片：同一用户的相同内容直接复用已有记录，不重复占用配额
+pub(crate) async fn ingest(
+    state: &AppState,
+    owner: uuid::Uuid,
+    data_bytes: Vec<u8>,
+) -> Result<MediaEntity, ApiError> {
+    let content_hash = hex::encode(Sha256::digest(&data_bytes));
+    match MediaServices::find_by_hash(&state.conn, owner, &content_hash).await? {
+        Some(media) => Ok(media),
+        None => store_media(state, owner, content_hash, data_bytes).await,
+    }
+}
+
+impl MediaController {
+    pub async fn upload_pic(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        mut multipart: Multipart,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let limits = &state.config.upload;
+        let mut image_url: Option<Vec<String>> = None;
+        let mut images = Vec::new();
+        let mut files = 0;
+
+        while let Some(mut field) = multipart.next_field().await? {
+            let name = field.name().map(|s| s.to_string()).unwrap_or_default();
+
+            // 處理圖片上傳，文件名只用来区分文件字段，不参与类型判断和对象命名
+            if name != "image" || field.file_name().is_none() {
+                continue;
+            }
+            files += 1;
+            if files > limits.max_files {
+                return Err(ApiError::BadRequest(format!(
+                    "at most {} images per request",
+                    limits.max_files
+                )));
+            }
+
+            // 边读边检查大小，超出上限立即中止，不把整个超大文件读进内存
+            let mut data_bytes: Vec<u8> = Vec::new();
+            while let Some(chunk) = field.chunk().await? {
+                if data_bytes.len() + chunk.len() > limits.max_file_bytes {
+                    return Err(ApiError::PayloadTooLarge(format!(
+                        "image exceeds {} bytes",
+                        limits.max_file_bytes
+                    )));
+                }
+                data_bytes.extend_from_slice(&chunk);
+            }
+
+            let media = ingest(&state, user.id, data_bytes).await?;
+
+            let media = media_json(&state, &media);
+            // image_url 兼容旧客户端
+            image_url
+                .get_or_insert_with(Vec::new)
+                .push(media["url"].as_str().unwrap_or_default().to_string());
+            images.push(media);
+        }
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "image_url": image_url,
+                "images": images,
+            })),
+            message: Some("Image uploaded successfully".to_string()),
+        };
+
+        Ok(Json(json!(data)))
+    }
+
+    pub async fn get_media(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let media = MediaServices::get_owned_media(&state.conn, id, user.id).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(media_json(&state, &media)),
+            message: Some("Media retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 按 media id 删除自己上传的图片及其全部尺寸，并归还配额；仍被 block 引用时返回 409
+    pub async fn delete_media(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let media = MediaServices::get_owned_media(&state.conn, id, user.id).await?;
+        // 仍被 block 引用的图片不能删除，否则 block 中的图片会失效
+        let dir = media
+            .object_key
+            .rsplit_once('/')
+            .map_or(media.object_key.as_str(), |(dir, _)| dir);
+        if BlockServices::references_media(&state.conn, dir).await? {
+            return Err(ApiError::Conflict(
+                "Media is still used by a block".to_string(),
+            ));
+        }
+        let media = MediaServices::delete_media_for_owner(&state.conn, media.id, user.id).await?;
+
+        let variants = variants_of(&media);
+        let objects = variants.len() as i32;
+        remove_objects(&state, variants.into_iter().map(|v| v.key)).await;
+        StorageServices::release(&state.conn, user.id, media.size, objects).await?;
+
+        let data = ResponseData::<Option<serde_json::Value>> {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: None,
+            message: Some("Media deleted successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 申请预签名 POST 表单，客户端直接把原图上传到对象存储，再调用 confirm 完成处理；本地存储不支持直传
+    /// 表单签名限定了 Content-Type 和 1 到 max_file_bytes 字节的大小，confirm 时再校验实际内容
+    pub async fn presign_upload(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        ValidatedJson(payload): ValidatedJson<PresignRequest>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let limits = &state.config.upload;
+        if !limits.allowed_types.contains(&payload.content_type) {
+            return Err(ApiError::UnsupportedMediaType(format!(
+                "unsupported image type: {}",
+                payload.content_type
+            )));
+        }
+        if payload.size > limits.max_file_bytes as u64 {
+            return Err(ApiError::PayloadTooLarge(format!(
+                "image exceeds {} bytes",
+                limits.max_file_bytes
+            )));
+        }
+        let usage = StorageServices::get_usage(&state.conn, user.id, limits.quota_bytes).await?;
+        if payload.size > usage.remaining_bytes as u64 {
+            return Err(ApiError::QuotaExceeded(
+                "Storage quota exceeded".to_string(),
+            ));
+        }
+
+        let upload_id = uuid::Uuid::new_v4();
+        let expires_in = state.config.minio.presign_expiry_secs;
+        let presigned = state
+            .store
+            .presign_post(
+                &imaging::staging_key(user.id, upload_id),
+                &payload.content_type,
+                limits.max_file_bytes as u64,
+                expires_in,
+            )
+            .await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "upload_id": upload_id,
+                "url": presigned.url,
+                "method": "POST",
+                "fields": presigned.fields,
+                "max_bytes": limits.max_file_bytes,
+                "expires_in": expires_in,
+            })),
+            message: Some("Upload URL created successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 确认直传完成：检查暂存对象存在且大小合规，再按 upload_pic 相同的流程识别类型、去除 EXIF、生成各尺寸
+    pub async fn confirm_upload(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        ValidatedJson(payload): ValidatedJson<ConfirmRequest>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let key = imaging::staging_key(user.id, payload.upload_id);
+
+        let stat = match state.store.stat(&key).await {
+            Ok(stat) => stat,
+            Err(StorageError::NotFound(_)) => {
+                return Err(ApiError::NotFound("Upload not found".to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let max_bytes = state.config.upload.max_file_bytes;
+        let result = if stat.size > max_bytes as u64 {
+            Err(ApiError::PayloadTooLarge(format!(
+                "image exceeds {} bytes",
+                max_bytes
+            )))
+        } else {
+            match state.store.get(&key).await {
+                Ok(bytes) => ingest(&state, user.id, bytes).await,
+                Err(e) => Err(e.into()),
+            }
+        };
+        // 无论处理成功与否，暂存的原图都不再需要
+        remove_objects(&state, [key]).await;
+        let media = result?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(media_json(&state, &media)),
+            message: Some("Image uploaded successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 私有图片的预签名 GET URL
+    pub async fn presign_download(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+        ValidatedQuery(params): ValidatedQuery<MediaUrlParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let media = MediaServices::get_owned_media(&state.conn, id, user.id).await?;
+
+        let key = match (params.variant, params.format) {
+            (None, None) => media.object_key.clone(),
+            (variant, format) => {
+                let variant =
+                    variant.or_else(|| state.config.image.variants.last().map(|v| v.name.clone()));
+                let format = format.or_else(|| state.config.image.formats.first().copied());
+                variants_of(&media)
+                    .into_iter()
+                    .find(|v| {
+                        Some(&v.name) == variant.as_ref()
+                            && Some(v.format.as_str()) == format.map(OutputFormat::as_str)
+                    })
+                    .map(|v| v.key)
+                    .ok_or_else(|| ApiError::NotFound("Variant not found".to_string()))?
+            }
+        };
+
+        let expires_in = state.config.minio.presign_expiry_secs;
+        let url = state.store.presign_get(&key, expires_in).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "url": url,
+                "expires_in": expires_in,
+            })),
+            message: Some("Download URL created successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+}
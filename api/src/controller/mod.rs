@@ -1,9 +1,14 @@
-
-
-/// 用户控制器模块
-pub(crate) mod user;
 /// 区块控制器模块
 pub(crate) mod block;
+/// 图片上传控制器模块
+pub(crate) mod media;
 /// 搜索历史控制器模块
 pub(crate) mod search_history;
-
+/// 存储用量控制器模块
+pub(crate) mod storage;
+/// token 刷新控制器模块
+pub(crate) mod token;
+/// 分片上传控制器模块
+pub(crate) mod upload;
+/// 用户控制器模块
+pub(crate) mod user;
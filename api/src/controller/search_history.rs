@@ -1,30 +1,41 @@
 use crate::{
+    error::ApiError,
+    extract::{ValidatedJson, ValidatedQuery},
     tools::{AppState, ResponseData, ResponseStatus},
 };
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
     response::Json,
     Extension,
 };
 use entity::users::Model as UserEntity;
-use service::{sea_orm::sqlx::types::uuid, search_history::SearchHistoryModel, SearchHistoryServices};
+use serde::Deserialize;
+use service::{
+    sea_orm::sqlx::types::uuid, search_history::SearchHistoryModel,
+    validation::validate_search_query, SearchHistoryServices,
+};
+use validator::Validate;
 
 use serde_json::json;
 use serde_json::to_value;
 
 pub struct SearchHistoryController;
 
-impl SearchHistoryController {
+#[derive(Deserialize, Validate)]
+pub struct SuggestParams {
+    #[validate(custom(function = "validate_search_query"))]
+    pub prefix: String,
+    #[validate(range(min = 1, max = 20, message = "limit must be between 1 and 20"))]
+    pub limit: Option<u64>,
+}
 
+impl SearchHistoryController {
     pub async fn get_search_history_by_uid(
         Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-
-        let history = SearchHistoryServices::get_search_history_by_uid(&state.conn, user.id)
-            .await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Cannot find search history"))?;
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let history =
+            SearchHistoryServices::get_search_history_by_uid(&state.conn, user.id).await?;
 
         let data = ResponseData {
             code: 200,
@@ -39,23 +50,19 @@ impl SearchHistoryController {
         println!("Json data: {:?}", json_data);
         Ok(Json(json!(json_data)))
     }
-    
+
     pub async fn create_search_history(
         Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
-        Json(payload): Json<SearchHistoryModel>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        SearchHistoryServices::create_search_history(&state.conn, payload, user.id)
-            .await
-            .map_err(|e| {
-                println!("Failed to create search history: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create search history")
-            })?;
+        ValidatedJson(payload): ValidatedJson<SearchHistoryModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let entry =
+            SearchHistoryServices::record_query(&state.conn, user.id, &payload.query).await?;
 
-        let data = ResponseData::<Option<serde_json::Value>> {
+        let data = ResponseData {
             code: 201,
             status: ResponseStatus::Success,
-            data: None,
+            data: Some(json!(entry)),
             message: Some("Search history created successfully".to_string()),
         };
 
@@ -64,26 +71,56 @@ impl SearchHistoryController {
         Ok(Json(json!(json_data)))
     }
 
-    pub async fn delete_all_search_history(
+    pub async fn suggest(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        ValidatedQuery(params): ValidatedQuery<SuggestParams>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let limit = params.limit.unwrap_or(10);
+        let suggestions =
+            SearchHistoryServices::suggest(&state.conn, user.id, &params.prefix, limit).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!({
+                "rows": suggestions
+            })),
+            message: Some("Suggestions retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 删除当前用户的一条搜索记录
+    pub async fn delete_search_history(
+        Extension(user): Extension<UserEntity>,
         State(state): State<AppState>,
-        Path(uid): Path<uuid::Uuid>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        SearchHistoryServices::delete_all_search_history_by_uid(&state.conn, uid)
-            .await
-            .map_err(|e| {
-                println!("Failed to delete all search history: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete all search history")
-            })?;
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        SearchHistoryServices::delete_search_history_entry(&state.conn, user.id, id).await?;
 
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 200,
             status: ResponseStatus::Success,
             data: None,
-            message: Some("All search history deleted successfully".to_string()),
+            message: Some("Search history deleted successfully".to_string()),
         };
+        Ok(Json(json!(data)))
+    }
 
-        let json_data = to_value(data).unwrap();
-        println!("Json data: {:?}", json_data);
-        Ok(Json(json!(json_data)))
+    /// 清空当前用户的搜索历史
+    pub async fn clear_search_history(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        SearchHistoryServices::delete_all_search_history_by_uid(&state.conn, user.id).await?;
+
+        let data = ResponseData::<Option<serde_json::Value>> {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: None,
+            message: Some("All search history deleted successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,31 @@
+use crate::{
+    error::ApiError,
+    tools::{AppState, ResponseData, ResponseStatus},
+};
+use axum::{extract::State, response::Json, Extension};
+use entity::users::Model as UserEntity;
+use service::StorageServices;
+
+use serde_json::json;
+
+pub struct StorageController;
+
+impl StorageController {
+    /// 当前用户的存储用量和配额
+    pub async fn usage(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let usage =
+            StorageServices::get_usage(&state.conn, user.id, state.config.upload.quota_bytes)
+                .await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!(usage)),
+            message: Some("Storage usage retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    error::ApiError,
+    middleware::auth::Auth,
+    tools::{AppState, ResponseData, ResponseStatus},
+};
+use axum::{extract::State, response::Json};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct RefreshTokenModel {
+    pub refresh_token: String,
+}
+
+pub struct TokenController;
+
+impl TokenController {
+    pub async fn refresh(
+        State(state): State<AppState>,
+        Json(payload): Json<RefreshTokenModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let tokens = Auth::refresh_token_pair(&state, &payload.refresh_token).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!(tokens)),
+            message: Some("Token refreshed successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+}
@@ -0,0 +1,406 @@
+use crate::{
+    controller::media::{ingest, media_json, remove_objects},
+    error::ApiError,
+    extract::ValidatedJson,
+    imaging,
+    object_store::UploadedPart,
+    tools::{AppState, ResponseData, ResponseStatus},
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    response::Json,
+    Extension,
+};
+use entity::{
+    media::Model as MediaEntity, upload_parts, upload_sessions, users::Model as UserEntity,
+};
+use serde::Deserialize;
+use service::{
+    media::{MediaVariant, NewMedia},
+    sea_orm::{prelude::DateTimeWithTimeZone, sqlx::types::uuid},
+    upload::{expected_part_size, part_count, NewUploadSession, UploadStatus},
+    MediaServices, StorageServices, UploadServices,
+};
+use sha2::{Digest, Sha256};
+use validator::Validate;
+
+use serde_json::json;
+
+pub struct UploadController;
+
+#[derive(Deserialize, Validate)]
+pub struct InitUploadRequest {
+    /// 文件类型，图片或配置中允许的视频类型
+    #[validate(length(
+        min = 1,
+        max = 100,
+        message = "content_type must be 1 to 100 characters"
+    ))]
+    pub content_type: String,
+    /// 文件大小，单位字节
+    #[validate(range(min = 1, message = "size must be positive"))]
+    pub size: u64,
+}
+
+fn is_video(session: &upload_sessions::Model) -> bool {
+    session.content_type.starts_with("video/")
+}
+
+fn expires_at(state: &AppState, session: &upload_sessions::Model) -> DateTimeWithTimeZone {
+    session.create_time + chrono::Duration::seconds(state.config.upload.session_ttl_secs as i64)
+}
+
+/// 上传进度：已上传的分片编号和字节数，客户端断线后据此只补传缺少的分片
+fn progress_json(
+    state: &AppState,
+    session: &upload_sessions::Model,
+    parts: &[upload_parts::Model],
+) -> serde_json::Value {
+    let expires_at = expires_at(state, session);
+    json!({
+        "upload_id": session.id,
+        "status": session.status,
+        "content_type": session.content_type,
+        "size": session.size,
+        "part_size": session.part_size,
+        "total_parts": part_count(session),
+        "uploaded_parts": parts.iter().map(|p| p.number).collect::<Vec<_>>(),
+        "uploaded_bytes": parts.iter().map(|p| p.size).sum::<i64>(),
+        "media_id": session.media_id,
+        "expires_at": expires_at,
+    })
+}
+
+fn ensure_uploading(session: &upload_sessions::Model) -> Result<(), ApiError> {
+    if session.status != UploadStatus::Uploading.as_str() {
+        return Err(ApiError::BadRequest("Upload already completed".to_string()));
+    }
+    Ok(())
+}
+
+/// 超过 session_ttl_secs 的会话即将被清理任务放弃，不再接受分片和合并
+fn ensure_not_expired(state: &AppState, session: &upload_sessions::Model) -> Result<(), ApiError> {
+    if expires_at(state, session) <= chrono::Utc::now() {
+        return Err(ApiError::Gone("Upload session expired".to_string()));
+    }
+    Ok(())
+}
+
+/// 合并后的视频直接作为 media 保存，不做转码；内容哈希是合并后整个文件的 SHA-256，与分片大小无关
+async fn store_video(
+    state: &AppState,
+    session: &upload_sessions::Model,
+) -> Result<MediaEntity, ApiError> {
+    let content_hash = match state.store.sha256(&session.object_key).await {
+        Ok(content_hash) => content_hash,
+        Err(e) => {
+            remove_objects(state, [session.object_key.clone()]).await;
+            StorageServices::release(&state.conn, session.owner, session.size, 1).await?;
+            return Err(e.into());
+        }
+    };
+
+    // 同一用户已上传过相同内容时复用已有记录，刚合并的对象和预占的配额都不再需要
+    if let Some(media) =
+        MediaServices::find_by_hash(&state.conn, session.owner, &content_hash).await?
+    {
+        remove_objects(state, [session.object_key.clone()]).await;
+        StorageServices::release(&state.conn, session.owner, session.size, 1).await?;
+        return Ok(media);
+    }
+
+    let ext = session.object_key.rsplit('.').next().unwrap_or_default();
+    let new = NewMedia {
+        id: session.id,
+        owner: session.owner,
+        object_key: session.object_key.clone(),
+        content_hash,
+        mime: session.content_type.clone(),
+        width: 0,
+        height: 0,
+        variants: vec![MediaVariant {
+            name: "original".to_string(),
+            format: ext.to_string(),
+            key: session.object_key.clone(),
+            width: 0,
+            height: 0,
+            bytes: session.size,
+        }],
+    };
+    let content_hash = new.content_hash.clone();
+    // 配额在 init 时已按文件大小预占，这里不再重复计入
+    let result = MediaServices::create_media(&state.conn, new).await;
+    if !matches!(result, Ok(Some(_))) {
+        remove_objects(state, [session.object_key.clone()]).await;
+        StorageServices::release(&state.conn, session.owner, session.size, 1).await?;
+    }
+    match result? {
+        Some(media) => Ok(media),
+        // 同一用户并发上传了相同内容，返回先写入的记录
+        None => MediaServices::find_by_hash(&state.conn, session.owner, &content_hash)
+            .await?
+            .ok_or_else(|| ApiError::Internal("conflicting media record not found".to_string())),
+    }
+}
+
+/// 合并后的图片是暂存对象，按 upload_pic 相同的流程处理成各尺寸后删除
+async fn store_image(
+    state: &AppState,
+    session: &upload_sessions::Model,
+) -> Result<MediaEntity, ApiError> {
+    // 正式图片由 ingest 按实际写入的字节数重新计入配额
+    StorageServices::release(&state.conn, session.owner, session.size, 1).await?;
+    let result = match state.store.get(&session.object_key).await {
+        Ok(bytes) => ingest(state, session.owner, bytes).await,
+        Err(e) => Err(e.into()),
+    };
+    remove_objects(state, [session.object_key.clone()]).await;
+    result
+}
+
+impl UploadController {
+    /// 创建分片上传会话：按声明的类型和大小检查并预占配额，返回分片大小和分片数
+    pub async fn init_upload(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        ValidatedJson(payload): ValidatedJson<InitUploadRequest>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let limits = &state.config.upload;
+        let id = uuid::Uuid::new_v4();
+        let object_key = if limits.allowed_types.contains(&payload.content_type) {
+            if payload.size > limits.max_file_bytes as u64 {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "image exceeds {} bytes",
+                    limits.max_file_bytes
+                )));
+            }
+            imaging::staging_key(user.id, id)
+        } else if limits.video_types.contains(&payload.content_type) {
+            if payload.size > limits.max_video_bytes {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "video exceeds {} bytes",
+                    limits.max_video_bytes
+                )));
+            }
+            imaging::video_key(user.id, id, &payload.content_type)
+        } else {
+            return Err(ApiError::UnsupportedMediaType(format!(
+                "unsupported upload type: {}",
+                payload.content_type
+            )));
+        };
+
+        let size = payload.size as i64;
+        StorageServices::reserve(&state.conn, user.id, size, 1, limits.quota_bytes).await?;
+        let storage_upload_id = match state
+            .store
+            .create_multipart(&object_key, &payload.content_type)
+            .await
+        {
+            Ok(upload_id) => upload_id,
+            Err(e) => {
+                StorageServices::release(&state.conn, user.id, size, 1).await?;
+                return Err(e.into());
+            }
+        };
+        let new = NewUploadSession {
+            id,
+            owner: user.id,
+            object_key: object_key.clone(),
+            storage_upload_id: storage_upload_id.clone(),
+            content_type: payload.content_type,
+            size,
+            part_size: limits.part_bytes as i64,
+        };
+        let session = match UploadServices::create_session(&state.conn, new).await {
+            Ok(session) => session,
+            Err(e) => {
+                if let Err(e) = state
+                    .store
+                    .abort_multipart(&object_key, &storage_upload_id)
+                    .await
+                {
+                    tracing::warn!("failed to abort multipart upload {}: {:?}", object_key, e);
+                }
+                StorageServices::release(&state.conn, user.id, size, 1).await?;
+                return Err(e.into());
+            }
+        };
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(progress_json(&state, &session, &[])),
+            message: Some("Upload created successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    pub async fn get_upload(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let session = UploadServices::get_owned_session(&state.conn, id, user.id).await?;
+        let parts = UploadServices::list_parts(&state.conn, session.id).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(progress_json(&state, &session, &parts)),
+            message: Some("Upload retrieved successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 上传一个分片，请求体为分片的原始字节；除最后一片外大小必须等于 part_size，重传同一编号会覆盖
+    pub async fn upload_part(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path((id, number)): Path<(uuid::Uuid, i32)>,
+        body: Bytes,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let session = UploadServices::get_owned_session(&state.conn, id, user.id).await?;
+        ensure_uploading(&session)?;
+        ensure_not_expired(&state, &session)?;
+        let expected = expected_part_size(&session, number).ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "part number must be between 1 and {}",
+                part_count(&session)
+            ))
+        })?;
+        if body.len() as i64 != expected {
+            return Err(ApiError::BadRequest(format!(
+                "part {} must be {} bytes",
+                number, expected
+            )));
+        }
+
+        // 第一片包含文件头，按内容识别类型，尽早拒绝与声明不符的文件
+        if number == 1 {
+            let limits = &state.config.upload;
+            if is_video(&session) {
+                let mime = imaging::sniff_video(&body, &limits.video_types)?;
+                if mime != session.content_type {
+                    return Err(ApiError::UnsupportedMediaType(format!(
+                        "content does not match {}",
+                        session.content_type
+                    )));
+                }
+            } else {
+                imaging::sniff(&body, &limits.allowed_types)?;
+            }
+        }
+
+        let sha256 = hex::encode(Sha256::digest(&body));
+        let etag = state
+            .store
+            .upload_part(
+                &session.object_key,
+                &session.storage_upload_id,
+                number as u16,
+                body.to_vec(),
+            )
+            .await?;
+        UploadServices::put_part(&state.conn, session.id, number, etag, expected, sha256).await?;
+        let parts = UploadServices::list_parts(&state.conn, session.id).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(progress_json(&state, &session, &parts)),
+            message: Some("Part uploaded successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 全部分片上传后合并为一个对象：视频直接保存为 media，图片按 upload_pic 的流程处理；重复调用返回同一个 media
+    pub async fn complete_upload(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let session = UploadServices::get_owned_session(&state.conn, id, user.id).await?;
+        let media = match session.media_id {
+            Some(media_id) => {
+                MediaServices::get_owned_media(&state.conn, media_id, user.id).await?
+            }
+            None => {
+                ensure_not_expired(&state, &session)?;
+                let parts = UploadServices::list_parts(&state.conn, session.id).await?;
+                let total = part_count(&session);
+                if parts.len() as i32 != total {
+                    let missing: Vec<i32> = (1..=total)
+                        .filter(|n| !parts.iter().any(|p| p.number == *n))
+                        .collect();
+                    return Err(ApiError::BadRequest(format!(
+                        "missing parts: {:?}",
+                        missing
+                    )));
+                }
+                let uploaded: Vec<UploadedPart> = parts
+                    .iter()
+                    .map(|part| UploadedPart {
+                        number: part.number as u16,
+                        etag: part.etag.clone(),
+                        size: part.size as u64,
+                    })
+                    .collect();
+                state
+                    .store
+                    .complete_multipart(&session.object_key, &session.storage_upload_id, &uploaded)
+                    .await?;
+
+                // 合并之后会话不能再继续上传：成功时标记完成，失败时删除会话，由客户端重新上传
+                let result = if is_video(&session) {
+                    store_video(&state, &session).await
+                } else {
+                    store_image(&state, &session).await
+                };
+                match result {
+                    Ok(media) => {
+                        UploadServices::mark_completed(&state.conn, session.id, media.id).await?;
+                        media
+                    }
+                    Err(e) => {
+                        UploadServices::delete_session(&state.conn, session.id).await?;
+                        return Err(e);
+                    }
+                }
+            }
+        };
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(media_json(&state, &media)),
+            message: Some("Upload completed successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    /// 放弃上传：删除已上传的分片和会话，并归还预占的配额
+    pub async fn abort_upload(
+        Extension(user): Extension<UserEntity>,
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let session = UploadServices::get_owned_session(&state.conn, id, user.id).await?;
+        ensure_uploading(&session)?;
+        state
+            .store
+            .abort_multipart(&session.object_key, &session.storage_upload_id)
+            .await?;
+        UploadServices::delete_session(&state.conn, session.id).await?;
+        StorageServices::release(&state.conn, user.id, session.size, 1).await?;
+
+        let data = ResponseData::<Option<serde_json::Value>> {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: None,
+            message: Some("Upload aborted successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+}
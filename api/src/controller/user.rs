@@ -1,18 +1,21 @@
 use crate::{
-    middleware::auth::Auth,
+    error::ApiError,
+    extract::{etag, IfMatch, ValidatedJson},
+    middleware::auth::{Auth, Claims},
     tools::{AppState, Params, ResponseData, ResponseStatus},
 };
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    response::{IntoResponse, Json},
+    Extension,
 };
+use entity::users::Model as UserEntity;
 use service::{
     sea_orm::sqlx::types::uuid,
-    user::{LoginModel, UserModel, UserServices},
+    user::{LoginModel, RoleModel, UserModel, UserPatch, UserServices},
 };
 
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
 use serde_json::to_value;
 
@@ -22,21 +25,16 @@ impl UserController {
     pub async fn list_users(
         state: State<AppState>,
         Query(params): Query<Params>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
+    ) -> Result<Json<serde_json::Value>, ApiError> {
         let page = params.page.unwrap_or(1);
         let posts_per_page = params.posts_per_page.unwrap_or(5);
 
-        let (users, num_pages) = UserServices::find_user(&state.conn, page, posts_per_page)
-            .await
-            .expect("Cannot find posts in page");
+        let users = UserServices::find_user(&state.conn, page, posts_per_page).await?;
 
         let data = ResponseData {
             code: 200,
             status: ResponseStatus::Success,
-            data: Some(json!({
-                "rows": users,
-                "num_pages": num_pages,
-            })),
+            data: Some(json!(users)),
             message: Some("Users retrieved successfully".to_string()),
         };
 
@@ -47,8 +45,8 @@ impl UserController {
 
     pub async fn create_user(
         state: State<AppState>,
-        Json(payload): Json<UserModel>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
+        ValidatedJson(payload): ValidatedJson<UserModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
         println!("Payload: {:?}", payload);
         // password md5
         // let payload = UserModel {
@@ -56,12 +54,7 @@ impl UserController {
         //         .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password"))?,
         //     ..payload
         // };
-        UserServices::create_user(&state.conn, payload)
-            .await
-            .map_err(|e| {
-                println!("Failed to create user: {:?}", e);
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create user")
-            })?;
+        UserServices::create_user(&state.conn, payload).await?;
 
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 201,
@@ -75,14 +68,14 @@ impl UserController {
     }
 
     pub async fn update_user(
+        Extension(current_user): Extension<UserEntity>,
         state: State<AppState>,
         Path(id): Path<uuid::Uuid>,
-        Json(payload): Json<UserModel>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
+        ValidatedJson(payload): ValidatedJson<UserModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        Auth::ensure_self_or_admin(&current_user, id)?;
         println!("Payload: {:?}", payload);
-        UserServices::update_user_by_id(&state.conn, id, payload)
-            .await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update user"))?;
+        UserServices::update_user_by_id(&state.conn, id, payload).await?;
 
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 200,
@@ -95,13 +88,34 @@ impl UserController {
         Ok(Json(json!(json_data)))
     }
 
+    /// 部分修改用户，只修改提交的字段；带 If-Match 时版本不一致返回 412
+    pub async fn patch_user(
+        Extension(current_user): Extension<UserEntity>,
+        state: State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+        IfMatch(expected): IfMatch,
+        ValidatedJson(payload): ValidatedJson<UserPatch>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        Auth::ensure_self_or_admin(&current_user, id)?;
+        let user = UserServices::patch_user_by_id(&state.conn, id, expected, payload).await?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!(user)),
+            message: Some("User updated successfully".to_string()),
+        };
+        Ok((etag(&user.updated_at), Json(json!(data))))
+    }
+
     pub async fn delete_user(
+        Extension(current_user): Extension<UserEntity>,
         state: State<AppState>,
         Path(id): Path<uuid::Uuid>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        UserServices::delete_user(&state.conn, id)
-            .await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete user"))?;
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        Auth::ensure_self_or_admin(&current_user, id)?;
+        UserServices::delete_user(&state.conn, id).await?;
+        Auth::revoke_all_sessions(&state, id).await?;
 
         let data = ResponseData::<Option<serde_json::Value>> {
             code: 200,
@@ -115,107 +129,68 @@ impl UserController {
     }
 
     pub async fn get_user_by_id(
+        Extension(current_user): Extension<UserEntity>,
         state: State<AppState>,
         Path(id): Path<uuid::Uuid>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
+    ) -> Result<impl IntoResponse, ApiError> {
+        Auth::ensure_self_or_admin(&current_user, id)?;
         let user = UserServices::find_user_by_id(&state.conn, id)
-            .await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to find user"))?;
-
-        let data = match user {
-            Some(user) => ResponseData {
-                code: 200,
-                status: ResponseStatus::Success,
-                data: Some(json!(user)),
-                message: Some("User retrieved successfully".to_string()),
-            },
-            None => ResponseData {
-                code: 404,
-                status: ResponseStatus::Error,
-                data: None,
-                message: Some("User not found".to_string()),
-            },
+            .await?
+            .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+        let data = ResponseData {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: Some(json!(user)),
+            message: Some("User retrieved successfully".to_string()),
         };
-        let json_data = to_value(data).unwrap();
-        println!("Json data: {:?}", json_data);
-        Ok(Json(json!(json_data)))
+        Ok((etag(&user.updated_at), Json(json!(data))))
     }
 
-    pub async fn login(
+    pub async fn update_user_role(
         state: State<AppState>,
-        Json(payload): Json<LoginModel>,
-    ) -> Result<Json<serde_json::Value>, (StatusCode, &'static str)> {
-        let js_code = &payload.js_code;
-        // Call WeChat jscode2session to exchange js_code for openid
-        let url = "https://api.weixin.qq.com/sns/jscode2session";
-        let req = Jscode2SessionRequest {
-            appid: "wxf17c75a716ce6ede".to_string(),
-            secret: "2c605b107f99f435b91963d67776f1d6".to_string(),
-            js_code: js_code.clone(),
-            grant_type: "authorization_code".to_string(),
+        Path(id): Path<uuid::Uuid>,
+        Json(payload): Json<RoleModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        UserServices::update_user_role(&state.conn, id, payload.role).await?;
+
+        let data = ResponseData::<Option<serde_json::Value>> {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: None,
+            message: Some("User role updated successfully".to_string()),
         };
+        Ok(Json(json!(data)))
+    }
 
-        println!("Request to WeChat jscode2session: {:?}", req);
-
-        let client = reqwest::Client::new();
-        let resp = client
-            .get(url)
-            .query(&req)
-            .send()
-            .await
-            .map_err(|_| (StatusCode::BAD_GATEWAY, "Failed to call jscode2session"))?
-            .json::<Jscode2SessionResponse>()
-            .await
-            .map_err(|_| (StatusCode::BAD_GATEWAY, "Invalid jscode2session response"))?;
-        println!("WeChat jscode2session response: {:?}", resp);
-        // If WeChat returns an error code
-        if resp.errcode.unwrap_or(0) != 0 {
-            return Err((StatusCode::BAD_GATEWAY, "WeChat jscode2session error"));
-        }
-
-        let openid = resp
-            .openid
-            .clone()
-            .ok_or((StatusCode::BAD_REQUEST, "Missing openid"))?;
+    pub async fn login(
+        state: State<AppState>,
+        ValidatedJson(payload): ValidatedJson<LoginModel>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        // Call WeChat jscode2session to exchange js_code for openid
+        let session = state.wechat.code2session(&payload.js_code).await?;
+        let openid = session.openid;
 
         // Find user by appid (openid); if not found, create a new user
-        let user = match UserServices::find_user_by_appid(&state.conn, &openid)
-            .await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to find user"))?
-        {
+        let user = match UserServices::find_user_by_appid(&state.conn, &openid).await? {
             Some(u) => u,
             None => {
                 println!("Creating new user with openid: {}", openid);
                 // create minimal user record using the appid as app_id
-                let _active_model = UserServices::create_user_with_appid(&state.conn, &openid)
-                    .await
-                    .map_err(|e| {
-                        println!("Failed to create user: {:?}", e);
-                        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create user")
-                    })?;
-                // Fetch the newly created user as Model
-                UserServices::find_user_by_appid(&state.conn, &openid)
-                    .await
-                    .map_err(|_| {
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Failed to fetch created user",
-                        )
-                    })?
-                    .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Created user not found"))?
+                UserServices::create_user_with_appid(&state.conn, &openid).await?
             }
         };
 
-        let token = Auth::encode_jwt(user.app_id.clone()).map_err(|_| {
-                        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to encode token")
-                    })?;
+        let tokens = Auth::issue_token_pair(&state, &user).await?;
         let data = ResponseData {
             code: 200,
             status: ResponseStatus::Success,
             data: Some(json!({
                 "user": user,
-                    "token": token,
-                "session_key": resp.session_key,
+                "token": tokens.token,
+                "refresh_token": tokens.refresh_token,
+                "expires_in": tokens.expires_in,
+                "session_key": session.session_key,
             })),
             message: Some("Login successful".to_string()),
         };
@@ -223,21 +198,42 @@ impl UserController {
         let json_data = to_value(data).unwrap();
         Ok(Json(json!(json_data)))
     }
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Jscode2SessionRequest {
-    appid: String,
-    secret: String,
-    js_code: String,
-    grant_type: String,
+    pub async fn logout(
+        Extension(user): Extension<UserEntity>,
+        Extension(claims): Extension<Claims>,
+        State(state): State<AppState>,
+        payload: Option<Json<LogoutModel>>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        let refresh_token = payload.and_then(|Json(p)| p.refresh_token);
+        Auth::logout(&state, &user, &claims, refresh_token.as_deref()).await?;
+
+        let data = ResponseData::<Option<serde_json::Value>> {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: None,
+            message: Some("Logout successful".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
+
+    pub async fn revoke_sessions(
+        State(state): State<AppState>,
+        Path(id): Path<uuid::Uuid>,
+    ) -> Result<Json<serde_json::Value>, ApiError> {
+        Auth::revoke_all_sessions(&state, id).await?;
+
+        let data = ResponseData::<Option<serde_json::Value>> {
+            code: 200,
+            status: ResponseStatus::Success,
+            data: None,
+            message: Some("User sessions revoked successfully".to_string()),
+        };
+        Ok(Json(json!(data)))
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Jscode2SessionResponse {
-    openid: Option<String>,
-    session_key: Option<String>,
-    unionid: Option<String>,
-    errcode: Option<i32>,
-    errmsg: Option<String>,
+#[derive(Deserialize, Debug)]
+pub struct LogoutModel {
+    pub refresh_token: Option<String>,
 }
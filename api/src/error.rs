@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{
+        multipart::MultipartError,
+        rejection::{JsonRejection, QueryRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use service::{sea_orm::DbErr, ServiceError};
+use thiserror::Error;
+use validator::ValidationErrors;
+
+use crate::{
+    imaging::ImagingError,
+    object_store::StorageError,
+    tools::{ResponseData, ResponseStatus},
+    wechat::WechatError,
+};
+
+/// 接口统一错误类型
+/// 响应体沿用 ResponseData 结构，`data.error_code` 为稳定的机器可读错误码
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("validation failed: {0}")]
+    InvalidFields(#[from] ValidationErrors),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+    #[error("{0}")]
+    QuotaExceeded(String),
+    #[error("{0}")]
+    Gone(String),
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("database error: {0}")]
+    Database(#[from] DbErr),
+    #[error("storage error: {0}")]
+    Storage(StorageError),
+    #[error("multipart error: {0}")]
+    Multipart(#[from] MultipartError),
+    #[error("upstream error: {0}")]
+    Upstream(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) | ApiError::Multipart(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) | ApiError::Database(DbErr::RecordNotFound(_)) => {
+                StatusCode::NOT_FOUND
+            }
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Gone(_) => StatusCode::GONE,
+            ApiError::PayloadTooLarge(_) | ApiError::QuotaExceeded(_) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Database(_) | ApiError::Storage(_) | ApiError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// 稳定的错误码，客户端应依据它而不是 message 做判断
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "BAD_REQUEST",
+            ApiError::Validation(_) | ApiError::InvalidFields(_) => "VALIDATION_FAILED",
+            ApiError::Unauthorized(_) => "UNAUTHORIZED",
+            ApiError::Forbidden(_) => "FORBIDDEN",
+            ApiError::NotFound(_) | ApiError::Database(DbErr::RecordNotFound(_)) => "NOT_FOUND",
+            ApiError::Conflict(_) => "CONFLICT",
+            ApiError::Gone(_) => "GONE",
+            ApiError::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            ApiError::UnsupportedMediaType(_) => "UNSUPPORTED_MEDIA_TYPE",
+            ApiError::QuotaExceeded(_) => "QUOTA_EXCEEDED",
+            ApiError::PreconditionFailed(_) => "PRECONDITION_FAILED",
+            ApiError::Database(_) => "DATABASE_ERROR",
+            ApiError::Storage(_) => "STORAGE_ERROR",
+            ApiError::Multipart(_) => "MULTIPART_ERROR",
+            ApiError::Upstream(_) => "UPSTREAM_ERROR",
+            ApiError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+
+    /// 返回给客户端的提示，内部错误细节只记录日志不对外暴露
+    fn public_message(&self) -> String {
+        match self {
+            ApiError::Database(DbErr::RecordNotFound(msg)) => msg.clone(),
+            ApiError::InvalidFields(_) => "Validation failed".to_string(),
+            ApiError::Database(_) => "Database error".to_string(),
+            ApiError::Storage(_) => "Storage error".to_string(),
+            ApiError::Upstream(_) => "Upstream service error".to_string(),
+            ApiError::Internal(_) => "Internal server error".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        if status.is_server_error() {
+            tracing::error!("{}", self);
+        }
+
+        let data = ResponseData {
+            code: status.as_u16() as i32,
+            status: ResponseStatus::Error,
+            data: Some(match &self {
+                ApiError::InvalidFields(errors) => json!({
+                    "error_code": self.error_code(),
+                    "fields": field_messages(errors),
+                }),
+                _ => json!({ "error_code": self.error_code() }),
+            }),
+            message: Some(self.public_message()),
+        };
+        (status, Json(data)).into_response()
+    }
+}
+
+/// 每个字段对应的错误提示列表，没有 message 的规则使用其 code
+fn field_messages(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|e| {
+                    e.message
+                        .as_ref()
+                        .map(|m| m.to_string())
+                        .unwrap_or_else(|| e.code.to_string())
+                })
+                .collect();
+            (field.to_string(), messages)
+        })
+        .collect()
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<ServiceError> for ApiError {
+    fn from(e: ServiceError) -> Self {
+        match e {
+            ServiceError::NotFound(msg) => ApiError::NotFound(msg.to_string()),
+            ServiceError::Forbidden(msg) => ApiError::Forbidden(msg.to_string()),
+            ServiceError::Invalid(msg) => ApiError::Validation(msg.to_string()),
+            ServiceError::QuotaExceeded(msg) => ApiError::QuotaExceeded(msg.to_string()),
+            ServiceError::PreconditionFailed(msg) => ApiError::PreconditionFailed(msg.to_string()),
+            ServiceError::Db(e) => ApiError::Database(e),
+        }
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(e: StorageError) -> Self {
+        match e {
+            StorageError::NotFound(_) => ApiError::NotFound("Object not found".to_string()),
+            StorageError::InvalidKey(_) | StorageError::Unsupported(_) => {
+                ApiError::BadRequest(e.to_string())
+            }
+            e => ApiError::Storage(e),
+        }
+    }
+}
+
+impl From<WechatError> for ApiError {
+    fn from(e: WechatError) -> Self {
+        match e {
+            WechatError::MissingOpenid => ApiError::BadRequest("Missing openid".to_string()),
+            e => ApiError::Upstream(e.to_string()),
+        }
+    }
+}
+
+impl From<ImagingError> for ApiError {
+    fn from(e: ImagingError) -> Self {
+        match e {
+            ImagingError::Decode(_) => ApiError::BadRequest(e.to_string()),
+            ImagingError::UnsupportedType(_) => ApiError::UnsupportedMediaType(e.to_string()),
+            ImagingError::TooManyPixels { .. } => ApiError::PayloadTooLarge(e.to_string()),
+            ImagingError::Encode(_) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
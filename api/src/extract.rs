@@ -0,0 +1,88 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Query, Request},
+    http::{header, request::Parts, HeaderName, HeaderValue},
+    Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use service::sea_orm::prelude::DateTimeWithTimeZone;
+use validator::Validate;
+
+use crate::error::ApiError;
+
+/// 反序列化 JSON 请求体并执行模型上声明的校验规则，
+/// 校验失败时直接返回逐字段的错误信息，不会进入 service 层
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+/// 与 ValidatedJson 相同，用于查询参数
+pub struct ValidatedQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        value.validate()?;
+        Ok(Self(value))
+    }
+}
+
+/// If-Match 请求头中的资源版本（update_time），没有提交或为 `*` 时为 None，表示不检查
+pub struct IfMatch(pub Option<DateTimeWithTimeZone>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(header::IF_MATCH) else {
+            return Ok(Self(None));
+        };
+        let value = value
+            .to_str()
+            .map_err(|_| ApiError::BadRequest("Invalid If-Match header".to_string()))?
+            .trim();
+        if value == "*" {
+            return Ok(Self(None));
+        }
+        let tag = value.trim_start_matches("W/").trim_matches('"');
+        DateTime::parse_from_rfc3339(tag)
+            .map(|version| Self(Some(version)))
+            .map_err(|_| ApiError::BadRequest("Invalid If-Match header".to_string()))
+    }
+}
+
+/// 资源版本对应的 ETag 响应头，客户端修改时放在 If-Match 中提交
+pub fn etag(version: &DateTimeWithTimeZone) -> [(HeaderName, HeaderValue); 1] {
+    let tag = format!(
+        "\"{}\"",
+        version
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Micros, true)
+    );
+    [(header::ETAG, HeaderValue::from_str(&tag).unwrap())]
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use entity::blocks;
+use serde::Serialize;
+use service::{
+    media::variants_of,
+    revision::BlockContent,
+    sea_orm::{sqlx::types::uuid::Uuid, DatabaseConnection, DbErr},
+    upload::UploadStatus,
+    BlockServices, MediaServices, RevisionServices, ServiceError, StorageServices, UploadServices,
+};
+
+use crate::{
+    config::GcSettings,
+    imaging::STAGING_PREFIX,
+    object_store::{ObjectInfo, ObjectStore},
+};
+
+/// 上传图片和视频所在的前缀
+const MEDIA_PREFIXES: [&str; 2] = ["images/", "videos/"];
+
+/// 一次孤儿图片清理的结果
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    /// 为 true 时只统计，没有实际删除
+    pub dry_run: bool,
+    /// 扫描的对象数
+    pub scanned: usize,
+    /// 删除（dry_run 时为将要删除）的对象 key
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+    /// 随对象一起删除的 media 记录
+    pub media_removed: Vec<Uuid>,
+    /// 超过有效期被清理的分片上传会话
+    pub uploads_expired: Vec<Uuid>,
+    /// 超过回收站保留期被永久删除的 block
+    pub blocks_purged: Vec<Uuid>,
+}
+
+/// 同一张图片的各尺寸位于同一目录 `images/{user_id}/{media_id}/`，按目录整体判断是否仍被引用；
+/// 旧版本上传的 `images/{user_id}/{文件名}` 以文件本身为单位
+fn group_of(key: &str) -> &str {
+    match key.match_indices('/').nth(2) {
+        Some((i, _)) => &key[..i],
+        None => key,
+    }
+}
+
+/// `images/{user_id}/{media_id}` 或 `videos/{user_id}/{media_id}` 对应的 media 所有者和 id
+fn media_of(group: &str) -> Option<(Uuid, Uuid)> {
+    let mut parts = MEDIA_PREFIXES
+        .iter()
+        .find_map(|prefix| group.strip_prefix(prefix))?
+        .split('/');
+    let owner = parts.next()?.parse().ok()?;
+    let id = parts.next()?.parse().ok()?;
+    Some((owner, id))
+}
+
+/// 从 block 中保存的图片 URL 还原对象 key；对外地址前缀改过时按路径中的 `/images/` 或 `/videos/` 定位
+fn key_of<'a>(base_url: &str, url: &'a str) -> Option<&'a str> {
+    url.strip_prefix(base_url).or_else(|| {
+        MEDIA_PREFIXES
+            .iter()
+            .find_map(|prefix| url.find(&format!("/{prefix}")))
+            .map(|i| &url[i + 1..])
+    })
+}
+
+/// 仍被 block（包括回收站中的 block 和历史版本）引用的图片目录
+async fn referenced_groups(
+    conn: &DatabaseConnection,
+    base_url: &str,
+) -> Result<HashSet<String>, DbErr> {
+    Ok(BlockServices::referenced_images(conn)
+        .await?
+        .iter()
+        .filter_map(|url| key_of(base_url, url))
+        .map(|key| group_of(key).to_string())
+        .collect())
+}
+
+/// 删除 media 记录、全部尺寸的对象并归还配额；记录已不存在时返回 None
+async fn remove_media(
+    conn: &DatabaseConnection,
+    store: &dyn ObjectStore,
+    owner: Uuid,
+    id: Uuid,
+) -> Result<Option<Uuid>, ServiceError> {
+    let media = match MediaServices::delete_media_for_owner(conn, id, owner).await {
+        Ok(media) => media,
+        Err(ServiceError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let variants = variants_of(&media);
+    for variant in &variants {
+        if let Err(e) = store.delete(&variant.key).await {
+            tracing::warn!("failed to delete object {}: {:?}", variant.key, e);
+        }
+    }
+    StorageServices::release(conn, owner, media.size, variants.len() as i32).await?;
+    Ok(Some(media.id))
+}
+
+/// 永久删除 block 及其历史版本，再删除其中引用的、已不被其他 block 使用的图片，返回删除的 media
+pub async fn purge_blocks(
+    conn: &DatabaseConnection,
+    store: &dyn ObjectStore,
+    blocks: &[blocks::Model],
+) -> Result<Vec<Uuid>, ServiceError> {
+    let base_url = store.public_url("");
+    let mut groups = BTreeSet::new();
+    for block in blocks {
+        let mut imgs = BlockContent::from(block).imgs.unwrap_or_default();
+        for revision in RevisionServices::list_revisions(conn, block.id).await? {
+            imgs.extend(BlockContent::from(&revision).imgs.unwrap_or_default());
+        }
+        // 只删除 block 作者自己上传的图片
+        if let Some(owner) = block
+            .pid
+            .as_deref()
+            .and_then(|pid| pid.parse::<Uuid>().ok())
+        {
+            groups.extend(
+                imgs.iter()
+                    .filter_map(|url| key_of(&base_url, url))
+                    .map(|key| group_of(key).to_string())
+                    .filter(|group| media_of(group).is_some_and(|(o, _)| o == owner)),
+            );
+        }
+        BlockServices::purge_block_by_id(conn, block.id).await?;
+    }
+    if groups.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let referenced = referenced_groups(conn, &base_url).await?;
+    let mut removed = Vec::new();
+    for group in groups.iter().filter(|group| !referenced.contains(*group)) {
+        if let Some((owner, id)) = media_of(group) {
+            removed.extend(remove_media(conn, store, owner, id).await?);
+        }
+    }
+    Ok(removed)
+}
+
+/// 永久删除在回收站中超过保留期的 block，返回 block 和随之删除的 media
+pub async fn purge_trash(
+    conn: &DatabaseConnection,
+    store: &dyn ObjectStore,
+    retention: Duration,
+    dry_run: bool,
+) -> anyhow::Result<(Vec<Uuid>, Vec<Uuid>)> {
+    let before = Utc::now() - chrono::Duration::from_std(retention)?;
+    let blocks = BlockServices::find_expired_trash(conn, before.into()).await?;
+    let ids = blocks.iter().map(|block| block.id).collect();
+    if dry_run || blocks.is_empty() {
+        return Ok((ids, Vec::new()));
+    }
+    let media = purge_blocks(conn, store, &blocks).await?;
+    Ok((ids, media))
+}
+
+/// 找出 `images/` 和 `videos/` 下没有被任何 block 引用、且最近一次写入已超过宽限期的图片，删除其全部尺寸和 media 记录并归还配额；
+/// `uploads/` 下超过宽限期仍未确认的暂存对象直接删除
+pub async fn collect_orphans(
+    conn: &DatabaseConnection,
+    store: &dyn ObjectStore,
+    grace_period: Duration,
+    dry_run: bool,
+) -> anyhow::Result<GcReport> {
+    // 先列对象再查引用：列举之后才保存的 block 也能被看到，不会误删刚被引用的图片；
+    // 没有 confirm 的暂存对象不计入配额，按同样的宽限期清理
+    let mut objects = Vec::new();
+    for prefix in MEDIA_PREFIXES.into_iter().chain([STAGING_PREFIX]) {
+        objects.extend(store.list(prefix).await?);
+    }
+    let referenced = referenced_groups(conn, &store.public_url("")).await?;
+
+    let cutoff = Utc::now() - chrono::Duration::from_std(grace_period)?;
+    let mut groups: BTreeMap<&str, Vec<&ObjectInfo>> = BTreeMap::new();
+    for object in &objects {
+        groups
+            .entry(group_of(&object.key))
+            .or_default()
+            .push(object);
+    }
+
+    let mut report = GcReport {
+        dry_run,
+        scanned: objects.len(),
+        ..Default::default()
+    };
+    for (group, objects) in groups {
+        if referenced.contains(group) || objects.iter().any(|o| o.last_modified > cutoff) {
+            continue;
+        }
+
+        if let Some((owner, id)) = media_of(group) {
+            // 先删记录再删对象：对象删除失败时下次清理会重试，不会留下指向空文件的记录
+            let media = if dry_run {
+                MediaServices::get_owned_media(conn, id, owner).await
+            } else {
+                MediaServices::delete_media_for_owner(conn, id, owner).await
+            };
+            match media {
+                Ok(media) => {
+                    if !dry_run {
+                        let count = variants_of(&media).len() as i32;
+                        StorageServices::release(conn, owner, media.size, count).await?;
+                    }
+                    report.media_removed.push(media.id);
+                }
+                Err(ServiceError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        for object in objects {
+            if !dry_run {
+                if let Err(e) = store.delete(&object.key).await {
+                    tracing::warn!("failed to delete orphaned object {}: {:?}", object.key, e);
+                    continue;
+                }
+            }
+            report.freed_bytes += object.size;
+            report.removed.push(object.key.clone());
+        }
+    }
+    Ok(report)
+}
+
+/// 清理创建超过 ttl 的分片上传会话：未完成的放弃已上传的分片并归还预占的配额，已完成的只删除会话记录
+pub async fn expire_uploads(
+    conn: &DatabaseConnection,
+    store: &dyn ObjectStore,
+    ttl: Duration,
+    dry_run: bool,
+) -> anyhow::Result<Vec<Uuid>> {
+    let before = Utc::now() - chrono::Duration::from_std(ttl)?;
+    let mut expired = Vec::new();
+    for session in UploadServices::find_expired(conn, before.into()).await? {
+        if !dry_run {
+            if session.status == UploadStatus::Uploading.as_str() {
+                if let Err(e) = store
+                    .abort_multipart(&session.object_key, &session.storage_upload_id)
+                    .await
+                {
+                    tracing::warn!("failed to abort upload {}: {:?}", session.id, e);
+                    continue;
+                }
+                StorageServices::release(conn, session.owner, session.size, 1).await?;
+            }
+            UploadServices::delete_session(conn, session.id).await?;
+        }
+        expired.push(session.id);
+    }
+    Ok(expired)
+}
+
+/// 依次清理过期的分片上传会话、回收站和孤儿文件
+pub async fn run(
+    conn: &DatabaseConnection,
+    store: &dyn ObjectStore,
+    settings: &GcSettings,
+    session_ttl: Duration,
+    dry_run: bool,
+) -> anyhow::Result<GcReport> {
+    let uploads_expired = expire_uploads(conn, store, session_ttl, dry_run).await?;
+    let retention = Duration::from_secs(settings.trash_retention_secs);
+    let (blocks_purged, purged_media) = purge_trash(conn, store, retention, dry_run).await?;
+    let grace_period = Duration::from_secs(settings.grace_period_secs);
+    let mut report = collect_orphans(conn, store, grace_period, dry_run).await?;
+    report.media_removed.extend(purged_media);
+    Ok(GcReport {
+        uploads_expired,
+        blocks_purged,
+        ..report
+    })
+}
+
+/// 启动后台清理任务，按 `gc.interval_secs` 定期执行
+pub fn spawn(
+    conn: Arc<DatabaseConnection>,
+    store: Arc<dyn ObjectStore>,
+    settings: GcSettings,
+    session_ttl: Duration,
+) {
+    let interval = Duration::from_secs(settings.interval_secs);
+    tokio::spawn(async move {
+        // 第一次在一个间隔之后执行，不拖慢启动
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            match run(&conn, store.as_ref(), &settings, session_ttl, false).await {
+                Ok(report) => tracing::info!(
+                    "media gc removed {} of {} objects ({} bytes), {} media records, {} expired uploads and {} trashed blocks",
+                    report.removed.len(),
+                    report.scanned,
+                    report.freed_bytes,
+                    report.media_removed.len(),
+                    report.uploads_expired.len(),
+                    report.blocks_purged.len()
+                ),
+                Err(e) => tracing::warn!("media gc failed: {:?}", e),
+            }
+        }
+    });
+}
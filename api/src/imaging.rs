@@ -0,0 +1,211 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits,
+};
+use serde::{Deserialize, Serialize};
+use service::sea_orm::sqlx::types::uuid::Uuid;
+use thiserror::Error;
+
+use crate::config::{ImageSettings, UploadSettings};
+
+/// 图片处理错误
+#[derive(Debug, Error)]
+pub enum ImagingError {
+    #[error("unsupported or corrupt image: {0}")]
+    Decode(#[source] image::ImageError),
+    #[error("failed to encode image: {0}")]
+    Encode(#[source] image::ImageError),
+    #[error("unsupported image type: {0}")]
+    UnsupportedType(String),
+    #[error("image dimensions {width}x{height} exceed the allowed size")]
+    TooManyPixels { width: u32, height: u32 },
+}
+
+/// 输出格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Webp,
+    Jpeg,
+}
+
+impl OutputFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpeg",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpg",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+/// 处理结果：原图摆正后的尺寸、按内容识别的类型和生成的各个变体
+#[derive(Debug)]
+pub struct ProcessedImage {
+    pub mime: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<EncodedVariant>,
+}
+
+/// 处理后的一个尺寸、一种格式的图片
+#[derive(Debug)]
+pub struct EncodedVariant {
+    pub name: String,
+    pub format: OutputFormat,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// 图片变体在对象存储中的 key：`images/{user_id}/{image_id}/{variant}.{ext}`
+pub fn object_key(user_id: Uuid, image_id: Uuid, variant: &str, format: OutputFormat) -> String {
+    format!(
+        "images/{}/{}/{}.{}",
+        user_id,
+        image_id,
+        variant,
+        format.extension()
+    )
+}
+
+/// 根据文件头的 magic bytes 判断图片类型，不信任客户端提供的文件名和 Content-Type
+pub fn sniff(bytes: &[u8], allowed_types: &[String]) -> Result<ImageFormat, ImagingError> {
+    let format = image::guess_format(bytes)
+        .map_err(|_| ImagingError::UnsupportedType("unknown".to_string()))?;
+    let mime = format.to_mime_type();
+    if !allowed_types.iter().any(|t| t == mime) {
+        return Err(ImagingError::UnsupportedType(mime.to_string()));
+    }
+    Ok(format)
+}
+
+/// 预签名直传暂存对象的前缀
+pub const STAGING_PREFIX: &str = "uploads/";
+
+/// 预签名直传的暂存对象：`uploads/{user_id}/{upload_id}`，确认后处理成正式图片并删除
+pub fn staging_key(user_id: Uuid, upload_id: Uuid) -> String {
+    format!("{}{}/{}", STAGING_PREFIX, user_id, upload_id)
+}
+
+/// 根据文件头判断视频类型：MP4/MOV 在偏移 4 处为 `ftyp` box，品牌 `qt  ` 为 QuickTime
+pub fn sniff_video(bytes: &[u8], allowed_types: &[String]) -> Result<&'static str, ImagingError> {
+    let mime = match bytes.get(4..12) {
+        Some([b'f', b't', b'y', b'p', b'q', b't', b' ', b' ']) => "video/quicktime",
+        Some([b'f', b't', b'y', b'p', ..]) => "video/mp4",
+        _ => return Err(ImagingError::UnsupportedType("unknown".to_string())),
+    };
+    if !allowed_types.iter().any(|t| t == mime) {
+        return Err(ImagingError::UnsupportedType(mime.to_string()));
+    }
+    Ok(mime)
+}
+
+/// 视频在对象存储中的 key：`videos/{user_id}/{video_id}/original.{ext}`
+pub fn video_key(user_id: Uuid, video_id: Uuid, content_type: &str) -> String {
+    let ext = match content_type {
+        "video/quicktime" => "mov",
+        _ => "mp4",
+    };
+    format!("videos/{}/{}/original.{}", user_id, video_id, ext)
+}
+
+/// 解码上传的图片，按 EXIF 方向摆正后生成配置中的各个尺寸和格式；
+/// 设置了 max_size 的尺寸固定输出 JPEG
+/// 输出由像素重新编码，原图中的 EXIF（包括 GPS 位置）等元数据不会保留
+pub fn process(
+    bytes: &[u8],
+    settings: &ImageSettings,
+    limits: &UploadSettings,
+) -> Result<ProcessedImage, ImagingError> {
+    let format = sniff(bytes, &limits.allowed_types)?;
+    let image = decode(bytes, format, limits.max_pixels)?;
+
+    let mut variants = Vec::with_capacity(settings.variants.len() * settings.formats.len());
+    for variant in &settings.variants {
+        let resized = match variant.max_size {
+            Some(max) if image.width() > max || image.height() > max => {
+                image.resize(max, max, FilterType::Lanczos3)
+            }
+            _ => image.clone(),
+        };
+        // 只有无损 WebP 编码可用，缩小后的尺寸用 WebP 往往比 JPEG 还大，只输出 JPEG
+        let formats: &[OutputFormat] = match variant.max_size {
+            Some(_) => &[OutputFormat::Jpeg],
+            None => &settings.formats,
+        };
+        for &format in formats {
+            variants.push(EncodedVariant {
+                name: variant.name.clone(),
+                format,
+                width: resized.width(),
+                height: resized.height(),
+                bytes: encode(&resized, format, settings.jpeg_quality)?,
+            });
+        }
+    }
+    Ok(ProcessedImage {
+        mime: format.to_mime_type(),
+        width: image.width(),
+        height: image.height(),
+        variants,
+    })
+}
+
+/// 解码前先读取文件头中的宽高，像素数超出上限时直接拒绝，防止小文件解压成超大图片耗尽内存
+fn decode(
+    bytes: &[u8],
+    format: ImageFormat,
+    max_pixels: u64,
+) -> Result<DynamicImage, ImagingError> {
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    let mut decode_limits = Limits::default();
+    // RGBA 每像素 4 字节，留出一倍余量给解码器的中间缓冲
+    decode_limits.max_alloc = Some(max_pixels.saturating_mul(8));
+    reader.limits(decode_limits);
+
+    let mut decoder = reader.into_decoder().map_err(ImagingError::Decode)?;
+    let (width, height) = decoder.dimensions();
+    if u64::from(width) * u64::from(height) > max_pixels {
+        return Err(ImagingError::TooManyPixels { width, height });
+    }
+    let orientation = decoder.orientation().map_err(ImagingError::Decode)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(ImagingError::Decode)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn encode(
+    image: &DynamicImage,
+    format: OutputFormat,
+    jpeg_quality: u8,
+) -> Result<Vec<u8>, ImagingError> {
+    let mut out = Vec::new();
+    match format {
+        // JPEG 不支持透明通道
+        OutputFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, jpeg_quality)),
+        OutputFormat::Webp => image
+            .to_rgba8()
+            .write_with_encoder(WebPEncoder::new_lossless(&mut out)),
+    }
+    .map_err(ImagingError::Encode)?;
+    Ok(out)
+}
@@ -1,38 +1,50 @@
+pub mod config;
 mod controller;
+pub mod error;
+pub mod extract;
+#[allow(dead_code)]
 mod flash;
-mod middleware;
-mod tools;
+pub mod gc;
+pub mod imaging;
+pub mod middleware;
+pub mod object_store;
+pub mod publisher;
+pub mod tools;
+pub mod wechat;
 
 use axum::{
-    http::{Method, StatusCode},
+    extract::DefaultBodyLimit,
+    http::{header, Method, StatusCode},
     middleware as axum_middleware,
-    routing::{delete, get, get_service, post},
+    routing::{delete, get, get_service, post, put},
     Router,
 };
 
-use middleware::auth::Auth;
+use middleware::{
+    auth::{Auth, JwtKeys},
+    revocation::RevocationList,
+};
 use migration::{Migrator, MigratorTrait};
 use service::sea_orm::Database;
 
-use std::{env, fmt::format};
-use tera::Tera;
+use std::{env, sync::Arc};
 use tower_cookies::CookieManagerLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
-use tracing;
 
-use crate::controller::block::BlockController;
+use crate::controller::media::MediaController;
 use crate::controller::search_history::SearchHistoryController;
+use crate::controller::storage::StorageController;
+use crate::controller::token::TokenController;
+use crate::controller::upload::UploadController;
 use crate::controller::user::UserController;
 
-use minio::s3::creds::StaticProvider;
-use minio::s3::http::BaseUrl;
-use minio::s3::{response::BucketExistsResponse, types::S3Api, Client, ClientBuilder};
-
+use config::{AppConfig, WechatProvider};
 use tools::AppState;
+use wechat::{FakeWechatAuthProvider, WechatAuthProvider, WechatClient, WechatConfig};
 
 /// 应用程序入口函数
-/// 负责初始化数据库连接、模板引擎和路由配置
+/// 负责初始化数据库连接、外部服务客户端和路由配置
 #[tokio::main]
 async fn start() -> anyhow::Result<()> {
     // 设置日志级别
@@ -40,76 +52,122 @@ async fn start() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let cors = CorsLayer::new()
         .allow_origin(Any) // 允许所有来源，生产环境建议指定具体来源
-        .allow_methods([Method::GET, Method::POST, Method::DELETE]) // 允许的 HTTP 方法
-        .allow_headers(Any); // 允许所有请求头
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ]) // 允许的 HTTP 方法
+        .allow_headers(Any) // 允许所有请求头
+        .expose_headers([header::ETAG]); // 客户端需要读取 ETag 作为 If-Match 提交
 
-    // 加载环境变量
+    // 加载配置：config.toml、config.{APP_ENV}.toml 与环境变量
     dotenvy::dotenv().ok();
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env file");
-    let host = env::var("HOST").expect("HOST is not set in .env file");
-    let port = env::var("PORT").expect("PORT is not set in .env file");
-    let server_url = format!("{host}:{port}");
-
-    // 初始化 MinIO 客戶端 (minio crate v0.3)
-    let base_url = "http://localhost:9000/".parse::<BaseUrl>()?;
-    let bucket = std::env::var("MINIO_BUCKET").unwrap_or_else(|_| "collection".to_string());
-
-    let static_provider = StaticProvider::new("minioadmin", "minioadmin", None);
-    let client = ClientBuilder::new(base_url.clone())
-        .provider(Some(Box::new(static_provider)))
-        .build()?;
-
-    let resp: BucketExistsResponse = client.bucket_exists(bucket.clone()).send().await?;
-    if !resp.exists {
-        client.create_bucket(bucket.clone()).send().await.unwrap();
+    let config = AppConfig::load()?;
+    tracing::debug!("loaded config: {:?}", config);
+    let jwt = JwtKeys::from_config(&config.jwt)?;
+
+    // 初始化微信登录接口，wechat.provider = "fake" 时使用不访问网络的假实现
+    let wechat: Arc<dyn WechatAuthProvider> = match config.wechat.provider {
+        WechatProvider::Fake => Arc::new(FakeWechatAuthProvider::new()),
+        WechatProvider::Official => {
+            Arc::new(WechatClient::new(WechatConfig::from(&config.wechat))?)
+        }
     };
 
+    // 初始化对象存储，storage.backend = "local" 时写入本地目录，不需要 MinIO
+    let store = object_store::from_config(&config)?;
+    store.init().await?;
+
     // 连接数据库并执行迁移
-    let conn = Database::connect(db_url)
-        .await
-        .expect("Database connection failed");
-    Migrator::up(&conn, None).await.unwrap();
+    let conn = Database::connect(config.database.url.expose()).await?;
+    Migrator::up(&conn, None).await?;
+    let conn = Arc::new(conn);
+
+    // 加载 token 吊销列表，并定期与数据库同步
+    let revocations = RevocationList::load(&conn).await?;
+    revocations
+        .clone()
+        .spawn_sync(conn.clone(), std::time::Duration::from_secs(60));
 
-    // 初始化模板引擎
-    let templates = Tera::new(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/**/*"))
-        .expect("Tera initialization failed");
+    // 定期清理没有被 block 引用的图片
+    if config.gc.enabled {
+        gc::spawn(
+            conn.clone(),
+            store.clone(),
+            config.gc.clone(),
+            std::time::Duration::from_secs(config.upload.session_ttl_secs),
+        );
+    }
 
-    let base_url = format!("http://localhost:9000/{}", bucket.clone());
+    // 定期发布到期的定时 block
+    publisher::spawn(
+        conn.clone(),
+        std::time::Duration::from_secs(config.publisher.interval_secs),
+    );
 
     // 创建应用状态
+    let server_addr = config.server.addr();
     let state = AppState {
-        templates,
         conn,
-        client,
-        bucket,
-        base_url,
+        store,
+        config: Arc::new(config),
+        jwt,
+        revocations,
+        wechat,
     };
 
-    // 配置路由
-    let app = Router::new()
+    let app = app(state).layer(cors);
+
+    // 启动服务器
+    let listener = tokio::net::TcpListener::bind(&server_addr).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+/// 构建应用路由，测试中可以直接传入自定义的 AppState
+pub fn app(state: AppState) -> Router {
+    let uploads_dir = state.config.storage.local.root.clone();
+    Router::new()
         // 用户认证相关路由
         .route("/api/login", post(UserController::login))
-        // 用户管理相关路由
+        .route("/api/token/refresh", post(TokenController::refresh))
         .route(
-            "/api/user",
-            get(UserController::list_users).layer(axum_middleware::from_fn_with_state(
+            "/api/logout",
+            post(UserController::logout).layer(axum_middleware::from_fn_with_state(
                 state.clone(),
                 Auth::authorization_middleware,
             )),
         )
+        // 用户管理相关路由
+        .route(
+            "/api/user",
+            get(UserController::list_users)
+                .layer(axum_middleware::from_fn(Auth::require_admin))
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
+        )
         .route(
             "/api/user/:id",
-            get(UserController::get_user_by_id).layer(axum_middleware::from_fn_with_state(
-                state.clone(),
-                Auth::authorization_middleware,
-            )),
+            get(UserController::get_user_by_id)
+                .patch(UserController::patch_user)
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
         )
         .route(
             "/api/user/new",
-            post(UserController::create_user).layer(axum_middleware::from_fn_with_state(
-                state.clone(),
-                Auth::authorization_middleware,
-            )),
+            post(UserController::create_user)
+                .layer(axum_middleware::from_fn(Auth::require_admin))
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
         )
         .route(
             "/api/user/update/:id",
@@ -125,6 +183,24 @@ async fn start() -> anyhow::Result<()> {
                 Auth::authorization_middleware,
             )),
         )
+        .route(
+            "/api/user/revoke_sessions/:id",
+            post(UserController::revoke_sessions)
+                .layer(axum_middleware::from_fn(Auth::require_admin))
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
+        )
+        .route(
+            "/api/user/role/:id",
+            post(UserController::update_user_role)
+                .layer(axum_middleware::from_fn(Auth::require_admin))
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
+        )
         // block 相关路由
         .route(
             "/api/block",
@@ -132,9 +208,54 @@ async fn start() -> anyhow::Result<()> {
                 axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
             ),
         )
+        .route(
+            "/api/block/search",
+            get(controller::block::BlockController::search_blocks).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/nearby",
+            get(controller::block::BlockController::nearby_blocks).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/trash",
+            get(controller::block::BlockController::trash_list).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/trash/:id",
+            delete(controller::block::BlockController::purge_block).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/trash/:id/restore",
+            post(controller::block::BlockController::restore_block).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
         .route(
             "/api/block/:id",
-            get(controller::block::BlockController::get_block).layer(
+            get(controller::block::BlockController::get_block)
+                .patch(controller::block::BlockController::patch_block)
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
+        )
+        .route(
+            "/api/me/blocks",
+            get(controller::block::BlockController::my_blocks).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/me/timeline",
+            get(controller::block::BlockController::my_timeline).layer(
                 axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
             ),
         )
@@ -150,12 +271,42 @@ async fn start() -> anyhow::Result<()> {
                 axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
             ),
         )
+        .route(
+            "/api/block/autosave/:id",
+            post(controller::block::BlockController::autosave_block).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
         .route(
             "/api/block/delete/:id",
             delete(controller::block::BlockController::delete_block).layer(
                 axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
             ),
         )
+        .route(
+            "/api/block/:id/revisions",
+            get(controller::block::BlockController::list_revisions).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/:id/revisions/diff",
+            get(controller::block::BlockController::diff_revisions).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/:id/revisions/:revision",
+            get(controller::block::BlockController::get_revision).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/block/:id/revisions/:revision/restore",
+            post(controller::block::BlockController::restore_revision).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
         .route(
             "/api/search_history",
             get(SearchHistoryController::get_search_history_by_uid).layer(
@@ -170,16 +321,115 @@ async fn start() -> anyhow::Result<()> {
         )
         .route(
             "/api/search_history/delete/:id",
-            delete(SearchHistoryController::delete_all_search_history).layer(
+            delete(SearchHistoryController::delete_search_history).layer(
                 axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
             ),
         )
-        .route("/api/upload_pic", post(controller::block::BlockController::upload_pic).layer(
-            axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
-        ))  
-        .route("/api/delete_pic", post(controller::block::BlockController::delete_pic).layer(
-            axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
-        ))
+        .route(
+            "/api/search_history/clear",
+            delete(SearchHistoryController::clear_search_history).layer(
+                axum_middleware::from_fn_with_state(state.clone(), Auth::authorization_middleware),
+            ),
+        )
+        .route(
+            "/api/search/suggest",
+            get(SearchHistoryController::suggest).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/upload_pic",
+            post(MediaController::upload_pic)
+                // axum 默认请求体上限为 2MB，按上传配置放宽，单个文件的大小在读取时另行检查
+                .layer(DefaultBodyLimit::max(
+                    state.config.upload.max_request_bytes(),
+                ))
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
+        )
+        .route(
+            "/api/media/presign",
+            post(MediaController::presign_upload).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/media/confirm",
+            post(MediaController::confirm_upload).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/media/:id/url",
+            get(MediaController::presign_download).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/media/:id",
+            get(MediaController::get_media).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/media/:id",
+            delete(MediaController::delete_media).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/uploads",
+            post(UploadController::init_upload).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/uploads/:id",
+            get(UploadController::get_upload).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/uploads/:id",
+            delete(UploadController::abort_upload).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/uploads/:id/parts/:number",
+            put(UploadController::upload_part)
+                // 请求体为单个分片，上限为分片大小
+                .layer(DefaultBodyLimit::max(state.config.upload.part_bytes))
+                .layer(axum_middleware::from_fn_with_state(
+                    state.clone(),
+                    Auth::authorization_middleware,
+                )),
+        )
+        .route(
+            "/api/uploads/:id/complete",
+            post(UploadController::complete_upload).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
+        .route(
+            "/api/storage/usage",
+            get(StorageController::usage).layer(axum_middleware::from_fn_with_state(
+                state.clone(),
+                Auth::authorization_middleware,
+            )),
+        )
         // 静态文件服务
         .nest_service(
             "/static",
@@ -196,27 +446,41 @@ async fn start() -> anyhow::Result<()> {
         )
         .nest_service(
             "/uploads",
-            get_service(ServeDir::new("./uploads")).handle_error(|error| async move {
+            get_service(ServeDir::new(uploads_dir)).handle_error(|error| async move {
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     format!("Unhandled internal error: {error}"),
                 )
             }),
         )
-        .layer(cors) // 添加 CORS 中间件
         .layer(CookieManagerLayer::new())
-        .with_state(state);
+        .with_state(state)
+}
 
-    // 启动服务器
-    let listener = tokio::net::TcpListener::bind(&server_url).await.unwrap();
-    axum::serve(listener, app).await?;
+/// 手动执行一次孤儿图片、过期上传会话和回收站清理，结果以 JSON 输出到标准输出
+#[tokio::main]
+async fn run_gc(dry_run: bool) -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    dotenvy::dotenv().ok();
+    let config = AppConfig::load()?;
 
+    let store = object_store::from_config(&config)?;
+    let conn = Database::connect(config.database.url.expose()).await?;
+    let session_ttl = std::time::Duration::from_secs(config.upload.session_ttl_secs);
+    let report = gc::run(&conn, store.as_ref(), &config.gc, session_ttl, dry_run).await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
     Ok(())
 }
 
 /// 程序入口点
+/// `gc [--dry-run]` 子命令手动清理孤儿图片，不带参数时启动服务
 pub fn main() {
-    let result = start();
+    let mut args = env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("gc") => run_gc(args.any(|arg| arg == "--dry-run")),
+        Some(command) => Err(anyhow::anyhow!("unknown command `{command}`")),
+        None => start(),
+    };
 
     if let Some(err) = result.err() {
         println!("Error: {err}");
@@ -1,39 +1,127 @@
-use std::sync::Arc;
+use std::collections::HashMap;
 
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
-    middleware::{self, Next},
+    middleware::Next,
     response::{IntoResponse, Response},
-    routing::get,
-    Router,
 };
 use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use entity::users::Model as UserEntity;
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData,
+    Validation,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use service::{
+    sea_orm::{prelude::DateTimeWithTimeZone, sqlx::types::uuid},
+    user::Role,
+    RefreshTokenServices, UserServices,
+};
 
-use service::UserServices;
-use thiserror::Error;
+use crate::{config::JwtSettings, error::ApiError, tools::AppState};
 
-use crate::tools::AppState;
+/// refresh token 的 typ 声明，用于和 access token 区分
+const REFRESH_TOKEN_TYPE: &str = "refresh";
 
-#[derive(Debug, Error)]
-pub struct AuthError {
-    pub message: String,
-    pub status_code: StatusCode,
+/// JWT 签名密钥集合
+/// 每把密钥通过 JWT header 中的 kid 区分，新 token 总是使用 active_kid 签名，
+/// 旧密钥保留在集合中即可继续校验已签发的 token，从而实现无感轮换
+#[derive(Clone)]
+pub struct JwtKeys {
+    active_kid: String,
+    secrets: HashMap<String, String>,
+    pub access_ttl: Duration,
+    pub refresh_ttl: Duration,
 }
 
-impl std::fmt::Display for AuthError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+impl JwtKeys {
+    /// active_kid 必须存在于 secrets 中
+    pub fn new(
+        active_kid: impl Into<String>,
+        secrets: HashMap<String, String>,
+        access_ttl: Duration,
+        refresh_ttl: Duration,
+    ) -> anyhow::Result<Self> {
+        let active_kid = active_kid.into();
+        if !secrets.contains_key(&active_kid) {
+            anyhow::bail!("active kid `{active_kid}` is not present in the JWT keys");
+        }
+        Ok(Self {
+            active_kid,
+            secrets,
+            access_ttl,
+            refresh_ttl,
+        })
+    }
+
+    /// 从应用配置加载密钥，只有一把密钥时可以不指定 active_kid
+    pub fn from_config(config: &JwtSettings) -> anyhow::Result<Self> {
+        let active_kid = match &config.active_kid {
+            Some(kid) => kid.clone(),
+            None => config
+                .keys
+                .keys()
+                .next()
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("jwt.keys is empty"))?,
+        };
+        let secrets = config
+            .keys
+            .iter()
+            .map(|(kid, secret)| (kid.clone(), secret.expose().to_string()))
+            .collect();
+
+        Self::new(
+            active_kid,
+            secrets,
+            Duration::minutes(config.access_ttl_minutes),
+            Duration::days(config.refresh_ttl_days),
+        )
     }
+
+    fn sign<T: Serialize>(&self, claims: &T) -> Result<String, ApiError> {
+        let secret = &self.secrets[&self.active_kid];
+        let header = Header {
+            kid: Some(self.active_kid.clone()),
+            ..Header::default()
+        };
+        encode(&header, claims, &EncodingKey::from_secret(secret.as_ref()))
+            .map_err(|e| ApiError::Internal(format!("failed to sign token: {e}")))
+    }
+
+    fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<TokenData<T>, ApiError> {
+        let header = decode_header(token).map_err(|_| invalid_token())?;
+        let secret = header
+            .kid
+            .as_ref()
+            .and_then(|kid| self.secrets.get(kid))
+            .ok_or_else(invalid_token)?;
+        decode(
+            token,
+            &DecodingKey::from_secret(secret.as_ref()),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| invalid_token())
+    }
+}
+
+fn invalid_token() -> ApiError {
+    ApiError::Unauthorized("Invalid or expired token".to_string())
 }
 
-pub struct Auth {
-    // 结构体内容
+/// 登录或刷新成功后返回给客户端的 token 对
+#[derive(Serialize)]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    /// access token 剩余有效秒数
+    pub expires_in: i64,
 }
 
+pub struct Auth;
+
 impl Auth {
     pub async fn authorization_middleware(
         state: State<AppState>,
@@ -42,90 +130,269 @@ impl Auth {
     ) -> Response {
         let auth_header = req.headers_mut().get(axum::http::header::AUTHORIZATION);
         // auth_header 轉字符串
-        let auth_header = match auth_header {
-            Some(header) => header.to_str().unwrap(),
+        let auth_header = match auth_header.and_then(|header| header.to_str().ok()) {
+            Some(header) => header,
             None => {
-                return Response::builder()
-                    .status(StatusCode::UNAUTHORIZED)
-                    .body(axum::body::Body::from("No authorization header provided"))
-                    .unwrap();
+                return ApiError::Unauthorized("No authorization header provided".to_string())
+                    .into_response();
             }
         };
-        let mut header = auth_header.split_whitespace();
-        let (bearer, token) = (header.next(), header.next());
-        let token_data = match Self::decode_jwt(token.unwrap().to_string()) {
+        let token = auth_header
+            .strip_prefix("Bearer ")
+            .unwrap_or(auth_header)
+            .trim();
+        let token_data = match Self::decode_jwt(&state.jwt, token) {
             Ok(data) => data,
-            Err(_) => {
-                return Response::builder()
-                    .status(StatusCode::UNAUTHORIZED)
-                    .body(axum::body::Body::from("Invalid or expired token"))
-                    .unwrap();
-            }
+            Err(e) => return e.into_response(),
         };
-        println!("Token data: {:?}", &token_data.claims.email);
+        if state.revocations.is_token_revoked(&token_data.claims.jti) {
+            return ApiError::Unauthorized("Token has been revoked".to_string()).into_response();
+        }
         let current_user =
             UserServices::find_user_by_appid(&state.conn, &token_data.claims.email).await;
         match current_user {
+            Ok(Some(user))
+                if state
+                    .revocations
+                    .is_user_revoked(&user.id, token_data.claims.issued_at_ms()) =>
+            {
+                return ApiError::Unauthorized("Token has been revoked".to_string())
+                    .into_response();
+            }
+            // 角色已变更的 token 需要通过 refresh 重新签发
+            Ok(Some(user)) if Role::from_db(&user.role) != token_data.claims.role => {
+                return ApiError::Unauthorized("Token role is outdated".to_string())
+                    .into_response();
+            }
             Ok(Some(user)) => {
                 req.extensions_mut().insert(user);
+                req.extensions_mut().insert(token_data.claims);
             }
             Ok(None) => {
-                return Response::builder()
-                    .status(StatusCode::UNAUTHORIZED)
-                    .body(axum::body::Body::from("User not found"))
-                    .unwrap();
-            }
-            Err(_) => {
-                return Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(axum::body::Body::from("Internal server error"))
-                    .unwrap();
+                return ApiError::Unauthorized("User not found".to_string()).into_response();
             }
+            Err(e) => return ApiError::from(e).into_response(),
         }
-        let response = next.run(req).await;
-        response
+        next.run(req).await
     }
 
+    #[allow(dead_code)]
     pub fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::BcryptError> {
         verify(password, hash)
     }
+    #[allow(dead_code)]
     pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
         let hash = hash(password, DEFAULT_COST)?;
         Ok(hash)
     }
 
-    pub fn encode_jwt(email: String) -> Result<String, StatusCode> {
-        let secret: String = "randomStringTypicallyFromEnv".to_string();
+    /// 角色校验中间件，需要放在 authorization_middleware 之后执行：
+    /// `.layer(from_fn(|req, next| Auth::require_role(Role::Admin, req, next)))`
+    pub async fn require_role(role: Role, req: Request, next: Next) -> Response {
+        match req.extensions().get::<Claims>() {
+            Some(claims) if claims.role == role || claims.role == Role::Admin => {
+                next.run(req).await
+            }
+            Some(_) => ApiError::Forbidden("Permission denied".to_string()).into_response(),
+            None => ApiError::Unauthorized("No authorization header provided".to_string())
+                .into_response(),
+        }
+    }
+
+    /// 仅管理员可访问的路由使用的中间件
+    pub async fn require_admin(req: Request, next: Next) -> Response {
+        Self::require_role(Role::Admin, req, next).await
+    }
+
+    /// 普通用户只能访问自己的记录，管理员可以访问任意用户
+    pub fn ensure_self_or_admin(user: &UserEntity, id: uuid::Uuid) -> Result<(), ApiError> {
+        if user.id == id || Role::from_db(&user.role) == Role::Admin {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden("Permission denied".to_string()))
+        }
+    }
+
+    pub fn encode_jwt(keys: &JwtKeys, email: String, role: Role) -> Result<String, ApiError> {
         let now = Utc::now();
-        let expire: chrono::TimeDelta = Duration::hours(24);
-        let exp: usize = (now + expire).timestamp() as usize;
+        let exp: usize = (now + keys.access_ttl).timestamp() as usize;
         let iat: usize = now.timestamp() as usize;
-        let claim = Claims { iat, exp, email };
+        let claim = Claims {
+            iat,
+            iat_ms: now.timestamp_millis(),
+            exp,
+            email,
+            jti: uuid::Uuid::new_v4(),
+            role,
+        };
 
-        encode(
-            &Header::default(),
-            &claim,
-            &EncodingKey::from_secret(secret.as_ref()),
-        )
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        keys.sign(&claim)
     }
 
-    pub fn decode_jwt(jwt_token: String) -> Result<TokenData<Claims>, StatusCode> {
-        let secret = "randomStringTypicallyFromEnv".to_string();
-        let result: Result<TokenData<Claims>, StatusCode> = decode(
-            &jwt_token,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &Validation::default(),
-        )
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
-        result
+    pub fn decode_jwt(keys: &JwtKeys, jwt_token: &str) -> Result<TokenData<Claims>, ApiError> {
+        keys.verify(jwt_token)
+    }
+
+    pub fn encode_refresh_token(
+        keys: &JwtKeys,
+        uid: uuid::Uuid,
+        jti: uuid::Uuid,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<String, ApiError> {
+        let claim = RefreshClaims {
+            exp: expires_at.timestamp() as usize,
+            iat: Utc::now().timestamp() as usize,
+            sub: uid,
+            jti,
+            typ: REFRESH_TOKEN_TYPE.to_string(),
+        };
+
+        keys.sign(&claim)
+    }
+
+    pub fn decode_refresh_token(
+        keys: &JwtKeys,
+        token: &str,
+    ) -> Result<TokenData<RefreshClaims>, ApiError> {
+        let data: TokenData<RefreshClaims> = keys.verify(token)?;
+        if data.claims.typ != REFRESH_TOKEN_TYPE {
+            return Err(invalid_token());
+        }
+        Ok(data)
+    }
+
+    /// 为用户签发新的 access token 和持久化的 refresh token
+    pub async fn issue_token_pair(
+        state: &AppState,
+        user: &UserEntity,
+    ) -> Result<TokenPair, ApiError> {
+        let expires_at = DateTimeWithTimeZone::from(Utc::now() + state.jwt.refresh_ttl);
+        let refresh =
+            RefreshTokenServices::create_refresh_token(&state.conn, user.id, expires_at).await?;
+
+        Self::sign_token_pair(state, user, refresh.id, refresh.expires_at)
+    }
+
+    /// 使用 refresh token 换取新的 token 对，旧的 refresh token 同时失效。
+    /// 已被吊销的 refresh token 再次出现说明可能被盗用，此时吊销该用户全部 refresh token
+    pub async fn refresh_token_pair(
+        state: &AppState,
+        refresh_token: &str,
+    ) -> Result<TokenPair, ApiError> {
+        let claims = Self::decode_refresh_token(&state.jwt, refresh_token)?.claims;
+        let stored = RefreshTokenServices::find_refresh_token_by_id(&state.conn, claims.jti)
+            .await?
+            .filter(|token| token.uid == claims.sub)
+            .ok_or_else(invalid_token)?;
+
+        if stored.revoked_at.is_some() {
+            RefreshTokenServices::revoke_all_refresh_tokens_by_uid(&state.conn, stored.uid).await?;
+            return Err(invalid_token());
+        }
+        if stored.expires_at < Utc::now() {
+            return Err(invalid_token());
+        }
+
+        let user = UserServices::find_user_by_id(&state.conn, stored.uid)
+            .await?
+            .ok_or_else(invalid_token)?;
+
+        let expires_at = DateTimeWithTimeZone::from(Utc::now() + state.jwt.refresh_ttl);
+        let uid = stored.uid;
+        let Some(refresh) =
+            RefreshTokenServices::rotate_refresh_token(&state.conn, stored, expires_at).await?
+        else {
+            // 同一个 token 已被并发的另一次刷新用掉，按重复使用处理
+            RefreshTokenServices::revoke_all_refresh_tokens_by_uid(&state.conn, uid).await?;
+            return Err(invalid_token());
+        };
+
+        Self::sign_token_pair(state, &user, refresh.id, refresh.expires_at)
+    }
+
+    /// 注销当前会话：吊销当前 access token，并吊销客户端提交的 refresh token
+    pub async fn logout(
+        state: &AppState,
+        user: &UserEntity,
+        claims: &Claims,
+        refresh_token: Option<&str>,
+    ) -> Result<(), ApiError> {
+        let expires_at = DateTime::from_timestamp(claims.exp as i64, 0)
+            .map(DateTimeWithTimeZone::from)
+            .ok_or_else(|| ApiError::BadRequest("Invalid token expiry".to_string()))?;
+        state
+            .revocations
+            .revoke_token(&state.conn, user.id, claims.jti, expires_at)
+            .await?;
+
+        if let Some(refresh_token) = refresh_token {
+            let refresh = Self::decode_refresh_token(&state.jwt, refresh_token)?.claims;
+            if refresh.sub != user.id {
+                return Err(ApiError::Forbidden(
+                    "Refresh token belongs to another user".to_string(),
+                ));
+            }
+            RefreshTokenServices::revoke_refresh_token(&state.conn, refresh.jti).await?;
+        }
+        Ok(())
+    }
+
+    /// 吊销用户的全部会话：已签发的 access token 立即失效，refresh token 全部作废
+    pub async fn revoke_all_sessions(state: &AppState, uid: uuid::Uuid) -> Result<(), ApiError> {
+        // 吊销记录只需保留到当前最长的 access token 过期为止
+        let expires_at = DateTimeWithTimeZone::from(Utc::now() + state.jwt.access_ttl);
+        state
+            .revocations
+            .revoke_user(&state.conn, uid, expires_at)
+            .await?;
+        RefreshTokenServices::revoke_all_refresh_tokens_by_uid(&state.conn, uid).await?;
+        Ok(())
+    }
+
+    fn sign_token_pair(
+        state: &AppState,
+        user: &UserEntity,
+        jti: uuid::Uuid,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<TokenPair, ApiError> {
+        Ok(TokenPair {
+            token: Self::encode_jwt(&state.jwt, user.app_id.clone(), Role::from_db(&user.role))?,
+            refresh_token: Self::encode_refresh_token(&state.jwt, user.id, jti, expires_at)?,
+            expires_in: state.jwt.access_ttl.num_seconds(),
+        })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 // Define a structure for holding claims data used in JWT tokens
 pub struct Claims {
-    pub exp: usize,    // Expiry time of the token
-    pub iat: usize,    // Issued at time of the token
+    pub exp: usize, // Expiry time of the token
+    pub iat: usize, // Issued at time of the token
+    #[serde(default)]
+    pub iat_ms: i64, // Issued at time in milliseconds, compared against user revocations
     pub email: String, // Email associated with the token
+    pub jti: uuid::Uuid, // Unique id of the token, used for revocation
+    #[serde(default)]
+    pub role: Role, // Role of the user when the token was issued
+}
+
+impl Claims {
+    /// 签发时间（毫秒），缺少 iat_ms 的旧 token 按 iat 换算
+    pub fn issued_at_ms(&self) -> i64 {
+        if self.iat_ms > 0 {
+            self.iat_ms
+        } else {
+            self.iat as i64 * 1000
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+// Claims carried by a refresh token; `jti` is the id of the persisted refresh_tokens row
+pub struct RefreshClaims {
+    pub exp: usize,
+    pub iat: usize,
+    pub sub: uuid::Uuid,
+    pub jti: uuid::Uuid,
+    pub typ: String,
 }
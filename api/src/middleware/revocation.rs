@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use service::{
+    sea_orm::{
+        prelude::DateTimeWithTimeZone, sqlx::types::uuid, DatabaseConnection, DbConn, DbErr,
+    },
+    TokenRevocationServices,
+};
+
+/// token 吊销列表：以 token_revocations 表为准，内存中缓存一份供鉴权中间件查询。
+/// 吊销时同时写库和缓存，后台任务定期从库中重新加载，使多实例部署最终一致
+#[derive(Clone, Default)]
+pub struct RevocationList {
+    inner: Arc<RwLock<RevocationCache>>,
+}
+
+#[derive(Default)]
+struct RevocationCache {
+    /// 单个被吊销的 token：jti -> 过期时间戳
+    tokens: HashMap<uuid::Uuid, i64>,
+    /// 整体吊销的用户：uid -> 吊销时间（毫秒），此前签发的 token 全部失效
+    users: HashMap<uuid::Uuid, i64>,
+}
+
+impl RevocationCache {
+    fn insert(&mut self, uid: uuid::Uuid, jti: Option<uuid::Uuid>, at: i64, expires_at: i64) {
+        match jti {
+            Some(jti) => {
+                self.tokens.insert(jti, expires_at);
+            }
+            None => {
+                let revoked_at = self.users.entry(uid).or_insert(at);
+                *revoked_at = (*revoked_at).max(at);
+            }
+        }
+    }
+}
+
+impl RevocationList {
+    pub async fn load(db: &DbConn) -> Result<Self, DbErr> {
+        let list = Self::default();
+        list.reload(db).await?;
+        Ok(list)
+    }
+
+    /// 从数据库重新加载全部未过期的吊销记录
+    pub async fn reload(&self, db: &DbConn) -> Result<(), DbErr> {
+        let mut cache = RevocationCache::default();
+        for row in TokenRevocationServices::find_active_revocations(db).await? {
+            cache.insert(
+                row.uid,
+                row.jti,
+                row.create_time.timestamp_millis(),
+                row.expires_at.timestamp(),
+            );
+        }
+        *self.inner.write().unwrap() = cache;
+        Ok(())
+    }
+
+    pub fn is_token_revoked(&self, jti: &uuid::Uuid) -> bool {
+        self.inner.read().unwrap().tokens.contains_key(jti)
+    }
+
+    /// 用户被整体吊销后，签发时间不晚于吊销时间的 token 均视为无效。
+    /// 按毫秒比较，避免吊销后同一秒内重新登录拿到的 token 也被拒绝
+    pub fn is_user_revoked(&self, uid: &uuid::Uuid, issued_at_ms: i64) -> bool {
+        self.inner
+            .read()
+            .unwrap()
+            .users
+            .get(uid)
+            .is_some_and(|revoked_at| issued_at_ms <= *revoked_at)
+    }
+
+    pub async fn revoke_token(
+        &self,
+        db: &DbConn,
+        uid: uuid::Uuid,
+        jti: uuid::Uuid,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<(), DbErr> {
+        let row =
+            TokenRevocationServices::create_revocation(db, uid, Some(jti), expires_at).await?;
+        self.inner.write().unwrap().insert(
+            uid,
+            Some(jti),
+            row.create_time.timestamp_millis(),
+            row.expires_at.timestamp(),
+        );
+        Ok(())
+    }
+
+    pub async fn revoke_user(
+        &self,
+        db: &DbConn,
+        uid: uuid::Uuid,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<(), DbErr> {
+        let row = TokenRevocationServices::create_revocation(db, uid, None, expires_at).await?;
+        self.inner.write().unwrap().insert(
+            uid,
+            None,
+            row.create_time.timestamp_millis(),
+            row.expires_at.timestamp(),
+        );
+        Ok(())
+    }
+
+    /// 启动后台同步任务：定期清理过期记录并重新加载缓存
+    pub fn spawn_sync(self, db: Arc<DatabaseConnection>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = TokenRevocationServices::delete_expired_revocations(&db).await {
+                    tracing::warn!("failed to prune token revocations: {:?}", e);
+                }
+                if let Err(e) = self.reload(&db).await {
+                    tracing::warn!("failed to reload token revocations: {:?}", e);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,697 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use axum::{body::Bytes, http::Method};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use minio::s3::{
+    builders::{ObjectContent, PostPolicy},
+    creds::StaticProvider,
+    error::{Error as MinioError, ErrorCode},
+    http::BaseUrl,
+    lifecycle_config::{LifecycleConfig, LifecycleRule},
+    multimap::Multimap,
+    response::BucketExistsResponse,
+    segmented_bytes::SegmentedBytes,
+    types::{Filter, PartInfo, S3Api, ToStream},
+    Client, ClientBuilder,
+};
+use serde::Serialize;
+use service::sea_orm::sqlx::types::uuid::Uuid;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    config::{AppConfig, MinioSettings, StorageBackend},
+    imaging::STAGING_PREFIX,
+};
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("object not found: {0}")]
+    NotFound(String),
+    #[error("invalid object key: {0}")]
+    InvalidKey(String),
+    #[error("{0} is not supported by this storage backend")]
+    Unsupported(&'static str),
+    #[error("s3 error: {0}")]
+    S3(Box<MinioError>),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<MinioError> for StorageError {
+    fn from(e: MinioError) -> Self {
+        StorageError::S3(Box::new(e))
+    }
+}
+
+/// 对象元数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectMeta {
+    pub size: u64,
+}
+
+/// 预签名直传的表单：客户端以 multipart/form-data 把 fields 和文件（字段名 `file`，放在最后）POST 到 url
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PresignedPost {
+    pub url: String,
+    pub fields: HashMap<String, String>,
+}
+
+/// 列举结果中的一个对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// 分片上传中已上传的一个分片，编号从 1 开始
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedPart {
+    pub number: u16,
+    pub etag: String,
+    pub size: u64,
+}
+
+/// 对象存储后端：MinIO/S3 或本地目录，由 `storage.backend` 配置选择
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// 启动时检查存储可用，必要时创建 bucket 或目录；MinIO 还会为 `uploads/` 设置 1 天过期的生命周期规则
+    async fn init(&self) -> Result<(), StorageError>;
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), StorageError>;
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError>;
+    /// 逐块读取对象并计算内容的 SHA-256（十六进制），不把整个对象读入内存
+    async fn sha256(&self, key: &str) -> Result<String, StorageError>;
+    /// 对象不存在时返回 `StorageError::NotFound`
+    async fn stat(&self, key: &str) -> Result<ObjectMeta, StorageError>;
+    /// 删除不存在的对象不算错误
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+    /// 列出 key 以 prefix 开头的全部对象
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError>;
+    /// 对象的公开访问地址
+    fn public_url(&self, key: &str) -> String;
+    /// 客户端直传用的预签名 POST 表单，签名限定了 key、Content-Type 和文件大小范围
+    async fn presign_post(
+        &self,
+        key: &str,
+        content_type: &str,
+        max_bytes: u64,
+        expiry_secs: u32,
+    ) -> Result<PresignedPost, StorageError>;
+    /// 私有对象的预签名 GET URL
+    async fn presign_get(&self, key: &str, expiry_secs: u32) -> Result<String, StorageError>;
+    /// 开始分片上传，返回存储侧的 upload id
+    async fn create_multipart(&self, key: &str, content_type: &str)
+        -> Result<String, StorageError>;
+    /// 上传一个分片，同一编号重复上传时覆盖，返回分片的 etag
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        number: u16,
+        bytes: Vec<u8>,
+    ) -> Result<String, StorageError>;
+    /// 按编号顺序把分片合并为 key 对应的对象
+    async fn complete_multipart(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> Result<(), StorageError>;
+    /// 放弃分片上传并删除已上传的分片，upload id 不存在时不算错误
+    async fn abort_multipart(&self, key: &str, upload_id: &str) -> Result<(), StorageError>;
+}
+
+/// 按 `storage.backend` 创建对象存储
+pub fn from_config(config: &AppConfig) -> Result<Arc<dyn ObjectStore>, StorageError> {
+    Ok(match config.storage.backend {
+        StorageBackend::Minio => Arc::new(MinioStore::from_settings(&config.minio)?),
+        StorageBackend::Local => Arc::new(LocalStore::new(
+            &config.storage.local.root,
+            config.storage.local.public_url(&config.server),
+        )),
+    })
+}
+
+/// MinIO / S3 后端
+pub struct MinioStore {
+    client: Client,
+    base_url: BaseUrl,
+    bucket: String,
+    public_base_url: String,
+}
+
+impl MinioStore {
+    /// base_url 与创建 client 时使用的地址相同，用于生成直传表单的提交地址
+    pub fn new(
+        client: Client,
+        base_url: BaseUrl,
+        bucket: impl Into<String>,
+        public_base_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            base_url,
+            bucket: bucket.into(),
+            public_base_url: public_base_url.into(),
+        }
+    }
+
+    pub fn from_settings(settings: &MinioSettings) -> Result<Self, StorageError> {
+        let base_url = settings.endpoint.parse::<BaseUrl>()?;
+        let static_provider =
+            StaticProvider::new(&settings.access_key, settings.secret_key.expose(), None);
+        let client = ClientBuilder::new(base_url.clone())
+            .provider(Some(Box::new(static_provider)))
+            .build()?;
+        Ok(Self::new(
+            client,
+            base_url,
+            &settings.bucket,
+            settings.public_url(),
+        ))
+    }
+}
+
+fn is_missing_object(e: &MinioError) -> bool {
+    matches!(e, MinioError::S3Error(e) if matches!(e.code, ErrorCode::NoSuchKey | ErrorCode::ResourceNotFound))
+}
+
+fn not_found_or(key: &str, e: MinioError) -> StorageError {
+    if is_missing_object(&e) {
+        StorageError::NotFound(key.to_string())
+    } else {
+        e.into()
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MinioStore {
+    async fn init(&self) -> Result<(), StorageError> {
+        let resp: BucketExistsResponse = self.client.bucket_exists(&self.bucket).send().await?;
+        if !resp.exists {
+            self.client.create_bucket(&self.bucket).send().await?;
+        }
+        // 预签名直传后未 confirm 的原图留在 uploads/ 下，交给生命周期规则 1 天后清理
+        let staging_rule = LifecycleRule {
+            id: "expire-staging-uploads".to_string(),
+            status: true,
+            filter: Filter {
+                and_operator: None,
+                prefix: Some(STAGING_PREFIX.to_string()),
+                tag: None,
+            },
+            expiration_days: Some(1),
+            ..Default::default()
+        };
+        self.client
+            .put_bucket_lifecycle(&self.bucket)
+            .life_cycle_config(LifecycleConfig {
+                rules: vec![staging_rule],
+            })
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), StorageError> {
+        self.client
+            .put_object_content(&self.bucket, key, ObjectContent::from(bytes))
+            .content_type(content_type.to_string())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        let object = self
+            .client
+            .get_object(&self.bucket, key)
+            .send()
+            .await
+            .map_err(|e| not_found_or(key, e))?;
+        Ok(object
+            .content
+            .to_segmented_bytes()
+            .await?
+            .to_bytes()
+            .to_vec())
+    }
+
+    async fn sha256(&self, key: &str) -> Result<String, StorageError> {
+        let object = self
+            .client
+            .get_object(&self.bucket, key)
+            .send()
+            .await
+            .map_err(|e| not_found_or(key, e))?;
+        let (mut stream, _) = object.content.to_stream().await?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = stream.next().await {
+            hasher.update(chunk?);
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    async fn stat(&self, key: &str) -> Result<ObjectMeta, StorageError> {
+        let stat = self
+            .client
+            .stat_object(&self.bucket, key)
+            .send()
+            .await
+            .map_err(|e| not_found_or(key, e))?;
+        Ok(ObjectMeta { size: stat.size })
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match self.client.delete_object(&self.bucket, key).send().await {
+            Err(e) if !is_missing_object(&e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError> {
+        let mut pages = self
+            .client
+            .list_objects(&self.bucket)
+            .prefix(Some(prefix.to_string()))
+            .recursive(true)
+            .to_stream()
+            .await;
+        let mut objects = Vec::new();
+        while let Some(page) = pages.next().await {
+            for entry in page?.contents {
+                if entry.is_prefix || entry.is_delete_marker {
+                    continue;
+                }
+                objects.push(ObjectInfo {
+                    key: entry.name,
+                    size: entry.size.unwrap_or_default(),
+                    last_modified: entry.last_modified.unwrap_or_else(Utc::now),
+                });
+            }
+        }
+        Ok(objects)
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base_url, key)
+    }
+
+    async fn presign_post(
+        &self,
+        key: &str,
+        content_type: &str,
+        max_bytes: u64,
+        expiry_secs: u32,
+    ) -> Result<PresignedPost, StorageError> {
+        let region = self
+            .client
+            .get_region_cached_async(&self.bucket, &None)
+            .await?;
+        let mut policy = PostPolicy::new(
+            &self.bucket,
+            Utc::now() + chrono::Duration::seconds(expiry_secs as i64),
+        )?;
+        policy.region = Some(region.clone());
+        policy.add_equals_condition("key", key)?;
+        policy.add_equals_condition("Content-Type", content_type)?;
+        policy.add_content_length_range_condition(1, max_bytes as usize)?;
+
+        let mut fields = self
+            .client
+            .get_presigned_post_form_data(policy)
+            .send()
+            .await?;
+        fields.insert("key".to_string(), key.to_string());
+        fields.insert("Content-Type".to_string(), content_type.to_string());
+        let url = self.base_url.build_url(
+            &Method::POST,
+            &region,
+            &Multimap::new(),
+            Some(&self.bucket),
+            None,
+        )?;
+        Ok(PresignedPost {
+            url: url.to_string(),
+            fields,
+        })
+    }
+
+    async fn presign_get(&self, key: &str, expiry_secs: u32) -> Result<String, StorageError> {
+        let presigned = self
+            .client
+            .get_presigned_object_url(&self.bucket, key, Method::GET)
+            .expiry_seconds(expiry_secs)
+            .send()
+            .await?;
+        Ok(presigned.url)
+    }
+
+    async fn create_multipart(
+        &self,
+        key: &str,
+        content_type: &str,
+    ) -> Result<String, StorageError> {
+        let resp = self
+            .client
+            .create_multipart_upload(&self.bucket, key)
+            .content_type(Some(content_type.to_string()))
+            .send()
+            .await?;
+        Ok(resp.upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        number: u16,
+        bytes: Vec<u8>,
+    ) -> Result<String, StorageError> {
+        let resp = self
+            .client
+            .upload_part(
+                &self.bucket,
+                key,
+                upload_id,
+                number,
+                SegmentedBytes::from(Bytes::from(bytes)),
+            )
+            .send()
+            .await?;
+        Ok(resp.etag)
+    }
+
+    async fn complete_multipart(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> Result<(), StorageError> {
+        let parts = parts
+            .iter()
+            .map(|part| PartInfo {
+                number: part.number,
+                etag: part.etag.clone(),
+                size: part.size,
+            })
+            .collect();
+        self.client
+            .complete_multipart_upload(&self.bucket, key, upload_id, parts)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn abort_multipart(&self, key: &str, upload_id: &str) -> Result<(), StorageError> {
+        match self
+            .client
+            .abort_multipart_upload(&self.bucket, key, upload_id)
+            .send()
+            .await
+        {
+            // minio crate 没有单独的 NoSuchUpload 错误码
+            Err(MinioError::S3Error(e))
+                if e.code == ErrorCode::OtherError("nosuchupload".to_string()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+/// 本地后端存放未完成分片的目录：与 root 同级的 `{root}.multipart`，不在 `/uploads` 静态目录之内
+fn multipart_root(root: &Path) -> PathBuf {
+    let root = std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf());
+    match root.file_name() {
+        Some(name) => {
+            let mut name = name.to_os_string();
+            name.push(".multipart");
+            root.with_file_name(name)
+        }
+        None => std::env::temp_dir().join("leafstore.multipart"),
+    }
+}
+
+/// 本地目录后端，文件通过 `/uploads` 静态目录对外访问，适合开发和测试
+pub struct LocalStore {
+    root: PathBuf,
+    multipart_root: PathBuf,
+    public_base_url: String,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>, public_base_url: impl Into<String>) -> Self {
+        let root = root.into();
+        Self {
+            multipart_root: multipart_root(&root),
+            root,
+            public_base_url: public_base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// 未完成分片所在的目录
+    pub fn multipart_root(&self) -> &Path {
+        &self.multipart_root
+    }
+
+    /// 分片上传的临时目录，upload id 由本地生成，必须是 uuid
+    fn parts_dir(&self, upload_id: &str) -> Result<PathBuf, StorageError> {
+        let upload_id: Uuid = upload_id
+            .parse()
+            .map_err(|_| StorageError::InvalidKey(upload_id.to_string()))?;
+        Ok(self.multipart_root.join(upload_id.to_string()))
+    }
+
+    /// key 只能是相对路径，不允许 `..` 等跳出根目录的写法
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        let relative = Path::new(key);
+        let valid = !key.is_empty()
+            && !key.contains('\\')
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !valid {
+            return Err(StorageError::InvalidKey(key.to_string()));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalStore {
+    async fn init(&self) -> Result<(), StorageError> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        Ok(())
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // 先写临时文件再改名，读取方不会看到写了一半的文件
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", Uuid::new_v4()));
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, StorageError> {
+        match tokio::fs::read(self.path(key)?).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(StorageError::NotFound(key.to_string()))
+            }
+            result => Ok(result?),
+        }
+    }
+
+    async fn sha256(&self, key: &str) -> Result<String, StorageError> {
+        let mut file = match tokio::fs::File::open(self.path(key)?).await {
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(StorageError::NotFound(key.to_string()))
+            }
+            result => result?,
+        };
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    async fn stat(&self, key: &str) -> Result<ObjectMeta, StorageError> {
+        match tokio::fs::metadata(self.path(key)?).await {
+            Ok(meta) if meta.is_file() => Ok(ObjectMeta { size: meta.len() }),
+            Ok(_) => Err(StorageError::NotFound(key.to_string())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(StorageError::NotFound(key.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>, StorageError> {
+        // 只遍历 prefix 所在的目录
+        let start = match prefix.rfind('/') {
+            Some(i) => self.path(&prefix[..i])?,
+            None => self.root.clone(),
+        };
+        let mut objects = Vec::new();
+        let mut dirs = vec![start];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let meta = entry.metadata().await?;
+                if meta.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+                // key 统一使用 `/` 分隔
+                let Ok(relative) = entry.path().strip_prefix(&self.root).map(Path::to_path_buf)
+                else {
+                    continue;
+                };
+                let key = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if key.starts_with(prefix) {
+                    objects.push(ObjectInfo {
+                        key,
+                        size: meta.len(),
+                        last_modified: meta
+                            .modified()
+                            .map(DateTime::from)
+                            .unwrap_or_else(|_| Utc::now()),
+                    });
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base_url, key)
+    }
+
+    async fn presign_post(
+        &self,
+        _key: &str,
+        _content_type: &str,
+        _max_bytes: u64,
+        _expiry_secs: u32,
+    ) -> Result<PresignedPost, StorageError> {
+        Err(StorageError::Unsupported("presigned upload"))
+    }
+
+    /// 本地目录没有带时效的签名地址，不能把私有对象当作公开地址返回
+    async fn presign_get(&self, _key: &str, _expiry_secs: u32) -> Result<String, StorageError> {
+        Err(StorageError::Unsupported("presigned download"))
+    }
+
+    async fn create_multipart(
+        &self,
+        key: &str,
+        _content_type: &str,
+    ) -> Result<String, StorageError> {
+        self.path(key)?;
+        let upload_id = Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(self.parts_dir(&upload_id)?).await?;
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        _key: &str,
+        upload_id: &str,
+        number: u16,
+        bytes: Vec<u8>,
+    ) -> Result<String, StorageError> {
+        let dir = self.parts_dir(upload_id)?;
+        if !tokio::fs::try_exists(&dir).await? {
+            return Err(StorageError::NotFound(upload_id.to_string()));
+        }
+        let etag = hex::encode(Sha256::digest(&bytes));
+        let tmp = dir.join(format!("{number}.{}.tmp", Uuid::new_v4()));
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, dir.join(number.to_string())).await?;
+        Ok(etag)
+    }
+
+    async fn complete_multipart(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        let dir = self.parts_dir(upload_id)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", Uuid::new_v4()));
+        let mut out = tokio::fs::File::create(&tmp).await?;
+        for part in parts {
+            let mut part_file = match tokio::fs::File::open(dir.join(part.number.to_string())).await
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    let _ = tokio::fs::remove_file(&tmp).await;
+                    return Err(StorageError::NotFound(format!(
+                        "{upload_id}/{}",
+                        part.number
+                    )));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            tokio::io::copy(&mut part_file, &mut out).await?;
+        }
+        out.sync_all().await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    async fn abort_multipart(&self, _key: &str, upload_id: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_dir_all(self.parts_dir(upload_id)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use service::{sea_orm::DatabaseConnection, BlockServices};
+
+/// 定期把 publish_at 已到的定时 block 改为已发布
+pub fn spawn(conn: Arc<DatabaseConnection>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match BlockServices::publish_due(&conn, Utc::now().into()).await {
+                Ok(result) if result.rows_affected > 0 => {
+                    tracing::info!("published {} scheduled blocks", result.rows_affected)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("publishing scheduled blocks failed: {:?}", e),
+            }
+        }
+    });
+}
@@ -1,28 +1,27 @@
 use serde::{Deserialize, Serialize};
 use service::sea_orm::DatabaseConnection;
-use tera::Tera;
-use minio::s3::Client;
+
+use std::sync::Arc;
+
+use crate::config::AppConfig;
+use crate::middleware::{auth::JwtKeys, revocation::RevocationList};
+use crate::object_store::ObjectStore;
+use crate::wechat::WechatAuthProvider;
+
 #[derive(Clone)]
 pub struct AppState {
-    pub templates: Tera,
-    pub conn: DatabaseConnection,
-    pub client: Client,
-    pub bucket: String,
-    pub base_url: String,
+    pub conn: Arc<DatabaseConnection>,
+    pub store: Arc<dyn ObjectStore>,
+    pub config: Arc<AppConfig>,
+    pub jwt: JwtKeys,
+    pub revocations: RevocationList,
+    pub wechat: Arc<dyn WechatAuthProvider>,
 }
 
 #[derive(Deserialize)]
 pub struct Params {
     pub page: Option<u64>,
     pub posts_per_page: Option<u64>,
-    pub q: Option<String>,
-    pub categories_id: Option<i32>,
-}
-
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct FlashData {
-    pub kind: String,
-    pub message: String,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -37,4 +36,4 @@ pub struct ResponseData<T> {
     pub code: i32,
     pub message: Option<String>,
     pub data: Option<T>,
-}
\ No newline at end of file
+}
@@ -0,0 +1,183 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::WechatSettings;
+
+/// 微信官方接口地址
+pub const DEFAULT_WECHAT_BASE_URL: &str = "https://api.weixin.qq.com";
+
+/// 微信返回的 errcode：系统繁忙，可以重试
+const ERRCODE_SYSTEM_BUSY: i32 = -1;
+
+/// code2session 换取到的会话信息
+#[derive(Debug, Clone)]
+pub struct WechatSession {
+    pub openid: String,
+    pub session_key: Option<String>,
+    pub unionid: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum WechatError {
+    #[error("failed to call jscode2session: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("jscode2session error {errcode}: {errmsg}")]
+    Api { errcode: i32, errmsg: String },
+    #[error("jscode2session response is missing openid")]
+    MissingOpenid,
+}
+
+/// 小程序登录凭证校验，把 wx.login 得到的 js_code 换成 openid
+#[async_trait]
+pub trait WechatAuthProvider: Send + Sync {
+    async fn code2session(&self, js_code: &str) -> Result<WechatSession, WechatError>;
+}
+
+/// 微信接口客户端配置
+#[derive(Clone)]
+pub struct WechatConfig {
+    pub base_url: String,
+    pub appid: String,
+    pub secret: String,
+    pub timeout: Duration,
+    /// 网络错误、5xx 或系统繁忙时的最大重试次数
+    pub retries: u32,
+}
+
+impl From<&WechatSettings> for WechatConfig {
+    fn from(settings: &WechatSettings) -> Self {
+        Self {
+            base_url: settings.base_url.clone(),
+            appid: settings.appid.clone(),
+            secret: settings.secret.expose().to_string(),
+            timeout: Duration::from_secs(settings.timeout_secs),
+            retries: settings.retries,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Jscode2SessionRequest<'a> {
+    appid: &'a str,
+    secret: &'a str,
+    js_code: &'a str,
+    grant_type: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct Jscode2SessionResponse {
+    openid: Option<String>,
+    session_key: Option<String>,
+    unionid: Option<String>,
+    errcode: Option<i32>,
+    errmsg: Option<String>,
+}
+
+/// 调用微信 jscode2session 接口的真实实现
+pub struct WechatClient {
+    http: reqwest::Client,
+    config: WechatConfig,
+}
+
+impl WechatClient {
+    pub fn new(config: WechatConfig) -> Result<Self, WechatError> {
+        let http = reqwest::Client::builder().timeout(config.timeout).build()?;
+        Ok(Self { http, config })
+    }
+
+    async fn request(&self, js_code: &str) -> Result<Jscode2SessionResponse, reqwest::Error> {
+        let url = format!(
+            "{}/sns/jscode2session",
+            self.config.base_url.trim_end_matches('/')
+        );
+        let req = Jscode2SessionRequest {
+            appid: &self.config.appid,
+            secret: &self.config.secret,
+            js_code,
+            grant_type: "authorization_code",
+        };
+        self.http
+            .get(url)
+            .query(&req)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Jscode2SessionResponse>()
+            .await
+    }
+}
+
+#[async_trait]
+impl WechatAuthProvider for WechatClient {
+    async fn code2session(&self, js_code: &str) -> Result<WechatSession, WechatError> {
+        let mut attempt = 0;
+        let resp = loop {
+            let result = self.request(js_code).await;
+            let retryable = match &result {
+                Ok(resp) => resp.errcode == Some(ERRCODE_SYSTEM_BUSY),
+                Err(e) => {
+                    e.is_connect()
+                        || e.is_timeout()
+                        || e.status().is_some_and(|s| s.is_server_error())
+                }
+            };
+            if !retryable || attempt >= self.config.retries {
+                break result?;
+            }
+            attempt += 1;
+            tracing::warn!("jscode2session attempt {attempt} failed, retrying");
+            tokio::time::sleep(Duration::from_millis(200 * u64::from(attempt))).await;
+        };
+
+        // If WeChat returns an error code
+        if let Some(errcode) = resp.errcode.filter(|code| *code != 0) {
+            return Err(WechatError::Api {
+                errcode,
+                errmsg: resp.errmsg.unwrap_or_default(),
+            });
+        }
+
+        Ok(WechatSession {
+            openid: resp.openid.ok_or(WechatError::MissingOpenid)?,
+            session_key: resp.session_key,
+            unionid: resp.unionid,
+        })
+    }
+}
+
+/// 本地开发和测试用的假实现，不访问网络。
+/// 预设过的 js_code 返回对应 openid，其余 js_code 返回 `fake-{js_code}`
+#[derive(Default)]
+pub struct FakeWechatAuthProvider {
+    sessions: HashMap<String, String>,
+}
+
+impl FakeWechatAuthProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_session(mut self, js_code: impl Into<String>, openid: impl Into<String>) -> Self {
+        self.sessions.insert(js_code.into(), openid.into());
+        self
+    }
+}
+
+#[async_trait]
+impl WechatAuthProvider for FakeWechatAuthProvider {
+    async fn code2session(&self, js_code: &str) -> Result<WechatSession, WechatError> {
+        let openid = self
+            .sessions
+            .get(js_code)
+            .cloned()
+            .unwrap_or_else(|| format!("fake-{js_code}"));
+        Ok(WechatSession {
+            openid,
+            session_key: Some("fake-session-key".to_string()),
+            unionid: None,
+        })
+    }
+}
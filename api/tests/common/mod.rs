@@ -0,0 +1,89 @@
+//! 集成测试共用的用户、JWT 和应用启动代码，各测试文件只提供自己的存储后端和配置
+#![allow(dead_code)]
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use api::{
+    config::AppConfig,
+    middleware::{
+        auth::{Auth, JwtKeys},
+        revocation::RevocationList,
+    },
+    object_store::ObjectStore,
+    tools::AppState,
+    wechat::{WechatClient, WechatConfig},
+};
+use axum::Router;
+use chrono::Utc;
+use entity::users;
+use service::{
+    sea_orm::{prelude::DateTimeWithTimeZone, sqlx::types::uuid::Uuid, DatabaseConnection},
+    user::Role,
+};
+
+pub fn jwt_keys() -> JwtKeys {
+    JwtKeys::new(
+        "test",
+        HashMap::from([("test".to_string(), "test-secret".to_string())]),
+        chrono::Duration::minutes(15),
+        chrono::Duration::days(30),
+    )
+    .unwrap()
+}
+
+pub fn user_model() -> users::Model {
+    let now = DateTimeWithTimeZone::from(Utc::now());
+    users::Model {
+        id: Uuid::new_v4(),
+        name: None,
+        sex: Some(0),
+        birthday: None,
+        phone: None,
+        email: None,
+        app_id: "openid-1".to_string(),
+        role: "user".to_string(),
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+/// 不需要登录的测试使用的微信配置，地址不可访问
+pub fn wechat_config() -> WechatConfig {
+    WechatConfig {
+        base_url: "http://127.0.0.1:9".to_string(),
+        appid: "appid".to_string(),
+        secret: "secret".to_string(),
+        timeout: Duration::from_secs(1),
+        retries: 0,
+    }
+}
+
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+/// 已登录用户的 access token
+pub fn access_token(user: &users::Model) -> String {
+    Auth::encode_jwt(&jwt_keys(), user.app_id.clone(), Role::User).unwrap()
+}
+
+/// 启动应用并返回地址
+pub async fn spawn_app(
+    conn: DatabaseConnection,
+    store: impl ObjectStore + 'static,
+    config: AppConfig,
+    wechat: WechatConfig,
+) -> String {
+    let state = AppState {
+        conn: Arc::new(conn),
+        store: Arc::new(store),
+        config: Arc::new(config),
+        jwt: jwt_keys(),
+        revocations: RevocationList::default(),
+        wechat: Arc::new(WechatClient::new(wechat).unwrap()),
+    };
+    serve(api::app(state)).await
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use api::{
+    gc,
+    object_store::{LocalStore, ObjectStore},
+};
+use chrono::Utc;
+use entity::{blocks, media, upload_sessions};
+use serde_json::json;
+use service::sea_orm::{
+    prelude::DateTimeWithTimeZone, sqlx::types::uuid::Uuid, DatabaseBackend, DatabaseConnection,
+    MockDatabase, MockExecResult, Value,
+};
+
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+struct Fixture {
+    root: PathBuf,
+    store: LocalStore,
+    owner: Uuid,
+    referenced: Uuid,
+    orphan: Uuid,
+    recent: Uuid,
+}
+
+async fn put(root: &Path, store: &LocalStore, key: &str, age: Duration) {
+    store.put(key, vec![0; 100], "image/jpeg").await.unwrap();
+    fs::File::options()
+        .write(true)
+        .open(root.join(key))
+        .unwrap()
+        .set_modified(SystemTime::now() - age)
+        .unwrap();
+}
+
+/// 三张新版图片（被引用、过期孤儿、刚上传）和一张旧版过期孤儿图片
+async fn fixture(name: &str) -> Fixture {
+    let root = std::env::temp_dir().join(format!("leafstore-gc-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let store = LocalStore::new(&root, "/uploads");
+    let _ = fs::remove_dir_all(store.multipart_root());
+    let fixture = Fixture {
+        root,
+        store,
+        owner: Uuid::new_v4(),
+        referenced: Uuid::new_v4(),
+        orphan: Uuid::new_v4(),
+        recent: Uuid::new_v4(),
+    };
+    let (root, store, owner) = (&fixture.root, &fixture.store, fixture.owner);
+    for variant in ["thumb.webp", "original.jpg"] {
+        let key = |id| format!("images/{owner}/{id}/{variant}");
+        put(root, store, &key(fixture.referenced), 2 * DAY).await;
+        put(root, store, &key(fixture.orphan), 2 * DAY).await;
+    }
+    put(
+        root,
+        store,
+        &format!("images/{owner}/{}/original.jpg", fixture.recent),
+        Duration::ZERO,
+    )
+    .await;
+    put(root, store, &format!("images/{owner}/legacy.jpg"), 2 * DAY).await;
+    fixture
+}
+
+fn referenced_rows(f: &Fixture) -> Vec<BTreeMap<&'static str, Value>> {
+    let url = format!("/uploads/images/{}/{}/original.jpg", f.owner, f.referenced);
+    vec![BTreeMap::from([("imgs", Value::from(json!([url])))])]
+}
+
+fn no_revisions() -> Vec<BTreeMap<&'static str, Value>> {
+    Vec::new()
+}
+
+fn orphan_media(f: &Fixture) -> media::Model {
+    media::Model {
+        id: f.orphan,
+        owner: f.owner,
+        object_key: format!("images/{}/{}/original.jpg", f.owner, f.orphan),
+        content_hash: "hash".to_string(),
+        size: 200,
+        mime: "image/jpeg".to_string(),
+        width: 10,
+        height: 10,
+        variants: json!([
+            { "name": "thumb", "format": "webp", "key": format!("images/{}/{}/thumb.webp", f.owner, f.orphan), "width": 10, "height": 10, "bytes": 100 },
+            { "name": "original", "format": "jpeg", "key": format!("images/{}/{}/original.jpg", f.owner, f.orphan), "width": 10, "height": 10, "bytes": 100 },
+        ]),
+        create_time: DateTimeWithTimeZone::from(Utc::now()),
+    }
+}
+
+async fn remaining(store: &LocalStore) -> usize {
+    store.list("images/").await.unwrap().len()
+}
+
+#[tokio::test]
+async fn orphans_past_the_grace_period_are_removed() {
+    let f = fixture("remove").await;
+    let conn: DatabaseConnection = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([referenced_rows(&f), no_revisions()])
+        .append_query_results([vec![orphan_media(&f)]])
+        .append_exec_results([
+            // 删除 media 记录、归还配额
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+        ])
+        .into_connection();
+
+    let report = gc::collect_orphans(&conn, &f.store, DAY, false)
+        .await
+        .unwrap();
+
+    assert_eq!(report.scanned, 6);
+    let mut removed = report.removed.clone();
+    removed.sort();
+    let mut expected = vec![
+        format!("images/{}/legacy.jpg", f.owner),
+        format!("images/{}/{}/original.jpg", f.owner, f.orphan),
+        format!("images/{}/{}/thumb.webp", f.owner, f.orphan),
+    ];
+    expected.sort();
+    assert_eq!(removed, expected);
+    assert_eq!(report.freed_bytes, 300);
+    assert_eq!(report.media_removed, [f.orphan]);
+    assert_eq!(remaining(&f.store).await, 3);
+
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains(r#"DELETE FROM \"media\""#));
+    assert!(log.contains(r#"UPDATE \"storage_usage\""#));
+}
+
+#[tokio::test]
+async fn unconfirmed_staging_uploads_are_removed() {
+    let f = fixture("staging").await;
+    let stale = format!("uploads/{}/{}", f.owner, Uuid::new_v4());
+    let fresh = format!("uploads/{}/{}", f.owner, Uuid::new_v4());
+    put(&f.root, &f.store, &stale, 2 * DAY).await;
+    put(&f.root, &f.store, &fresh, Duration::ZERO).await;
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([referenced_rows(&f), no_revisions()])
+        .append_query_results([vec![orphan_media(&f)]])
+        .append_exec_results(vec![
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            };
+            2
+        ])
+        .into_connection();
+
+    let report = gc::collect_orphans(&conn, &f.store, DAY, false)
+        .await
+        .unwrap();
+
+    assert_eq!(report.scanned, 8);
+    assert!(report.removed.contains(&stale));
+    assert!(!report.removed.contains(&fresh));
+    let staged: Vec<_> = f
+        .store
+        .list("uploads/")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|o| o.key)
+        .collect();
+    assert_eq!(staged, [fresh]);
+}
+
+#[tokio::test]
+async fn dry_run_only_reports() {
+    let f = fixture("dry-run").await;
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([referenced_rows(&f), no_revisions()])
+        .append_query_results([vec![orphan_media(&f)]])
+        .into_connection();
+
+    let report = gc::collect_orphans(&conn, &f.store, DAY, true)
+        .await
+        .unwrap();
+
+    assert!(report.dry_run);
+    assert_eq!(report.removed.len(), 3);
+    assert_eq!(report.media_removed, [f.orphan]);
+    assert_eq!(remaining(&f.store).await, 6);
+}
+
+#[tokio::test]
+async fn expired_upload_sessions_are_aborted() {
+    let f = fixture("expire").await;
+    let key = format!("videos/{}/{}/original.mp4", f.owner, f.orphan);
+    let storage_upload_id = f.store.create_multipart(&key, "video/mp4").await.unwrap();
+    let created = DateTimeWithTimeZone::from(Utc::now() - chrono::Duration::days(2));
+    let session = |status: &str, storage_upload_id: String| upload_sessions::Model {
+        id: Uuid::new_v4(),
+        owner: f.owner,
+        object_key: key.clone(),
+        storage_upload_id,
+        content_type: "video/mp4".to_string(),
+        size: 100,
+        part_size: 16,
+        status: status.to_string(),
+        media_id: None,
+        create_time: created,
+        update_time: created,
+    };
+    let uploading = session("uploading", storage_upload_id);
+    let completed = session("completed", Uuid::new_v4().to_string());
+    let exec = MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    };
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![uploading.clone(), completed.clone()]])
+        // 未完成的会话归还配额并删除分片和会话记录，已完成的只删除记录
+        .append_exec_results(vec![exec.clone(); 5])
+        .into_connection();
+
+    let expired = gc::expire_uploads(&conn, &f.store, DAY, false)
+        .await
+        .unwrap();
+
+    assert_eq!(expired, [uploading.id, completed.id]);
+    assert_eq!(fs::read_dir(f.store.multipart_root()).unwrap().count(), 0);
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains(r#"UPDATE \"storage_usage\""#));
+    assert!(log.contains(r#"DELETE FROM \"upload_sessions\""#));
+}
+
+#[tokio::test]
+async fn expired_trash_is_purged_with_its_images() {
+    let f = fixture("trash").await;
+    let deleted_at = DateTimeWithTimeZone::from(Utc::now() - chrono::Duration::days(40));
+    let block = blocks::Model {
+        id: Uuid::new_v4(),
+        pid: Some(f.owner.to_string()),
+        context: Some("trashed".to_string()),
+        imgs: Some(json!([format!(
+            "/uploads/images/{}/{}/original.jpg",
+            f.owner, f.orphan
+        )])),
+        location: None,
+        latitude: None,
+        longitude: None,
+        draft: Some(false),
+        visibility: "private".to_string(),
+        status: "published".to_string(),
+        publish_at: None,
+        published_at: Some(deleted_at),
+        create_time: deleted_at,
+        update_time: deleted_at,
+        deleted_at: Some(deleted_at),
+    };
+    let exec = MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    };
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![block.clone()]])
+        .append_query_results([Vec::<entity::block_revisions::Model>::new()])
+        // 永久删除后图片不再被任何 block 引用
+        .append_query_results([no_revisions(), no_revisions()])
+        .append_query_results([vec![orphan_media(&f)]])
+        // 删除历史版本、block、media 记录，归还配额
+        .append_exec_results(vec![exec; 4])
+        .into_connection();
+
+    let (blocks, media) = gc::purge_trash(&conn, &f.store, 30 * DAY, false)
+        .await
+        .unwrap();
+
+    assert_eq!(blocks, [block.id]);
+    assert_eq!(media, [f.orphan]);
+    assert_eq!(remaining(&f.store).await, 4);
+    let log = format!("{:?}", conn.into_transaction_log());
+    assert!(log.contains(r#"DELETE FROM \"blocks\""#));
+    assert!(log.contains(r#"DELETE FROM \"block_revisions\""#));
+    assert!(log.contains(r#"DELETE FROM \"media\""#));
+}
@@ -0,0 +1,189 @@
+use std::io::Cursor;
+
+use api::{
+    config::{ImageSettings, UploadSettings},
+    imaging::{self, ImagingError, OutputFormat},
+};
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    ImageFormat, RgbImage,
+};
+
+fn jpeg(width: u32, height: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    RgbImage::from_pixel(width, height, image::Rgb([200, 80, 40]))
+        .write_with_encoder(JpegEncoder::new(&mut out))
+        .unwrap();
+    out
+}
+
+/// 带噪点的图片，接近照片，无损压缩的效果很差
+fn noisy_jpeg(width: u32, height: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    RgbImage::from_fn(width, height, |x, y| {
+        let n = (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)).wrapping_mul(2_654_435_761);
+        image::Rgb([(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8])
+    })
+    .write_with_encoder(JpegEncoder::new(&mut out))
+    .unwrap();
+    out
+}
+
+fn process(bytes: &[u8]) -> Result<Vec<imaging::EncodedVariant>, ImagingError> {
+    imaging::process(bytes, &ImageSettings::default(), &UploadSettings::default())
+        .map(|image| image.variants)
+}
+
+/// 在 JPEG 的 SOI 之后插入只含 Orientation 标签的 EXIF 段
+fn with_exif_orientation(jpeg: &[u8], orientation: u16) -> Vec<u8> {
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    tiff.extend_from_slice(&0x0112u16.to_le_bytes());
+    tiff.extend_from_slice(&3u16.to_le_bytes());
+    tiff.extend_from_slice(&1u32.to_le_bytes());
+    tiff.extend_from_slice(&(orientation as u32).to_le_bytes());
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut payload = b"Exif\0\0".to_vec();
+    payload.extend_from_slice(&tiff);
+
+    let mut out = jpeg[..2].to_vec();
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(&payload);
+    out.extend_from_slice(&jpeg[2..]);
+    out
+}
+
+#[test]
+fn variants_are_resized_in_every_format() {
+    let variants = process(&jpeg(2000, 1000)).unwrap();
+
+    let sizes: Vec<_> = variants
+        .iter()
+        .map(|v| (v.name.as_str(), v.format, v.width, v.height))
+        .collect();
+    assert_eq!(
+        sizes,
+        [
+            ("thumb", OutputFormat::Jpeg, 320, 160),
+            ("medium", OutputFormat::Jpeg, 1080, 540),
+            ("original", OutputFormat::Webp, 2000, 1000),
+            ("original", OutputFormat::Jpeg, 2000, 1000),
+        ]
+    );
+    for variant in &variants {
+        let format = image::guess_format(&variant.bytes).unwrap();
+        let expected = match variant.format {
+            OutputFormat::Webp => ImageFormat::WebP,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+        };
+        assert_eq!(format, expected);
+    }
+}
+
+#[test]
+fn resized_variants_are_smaller_than_lossless_webp() {
+    let variants = process(&noisy_jpeg(640, 480)).unwrap();
+
+    for name in ["thumb", "medium"] {
+        let resized: Vec<_> = variants.iter().filter(|v| v.name == name).collect();
+        assert_eq!(resized.len(), 1);
+        assert_eq!(resized[0].format, OutputFormat::Jpeg);
+
+        let mut webp = Vec::new();
+        image::load(Cursor::new(&resized[0].bytes), ImageFormat::Jpeg)
+            .unwrap()
+            .to_rgba8()
+            .write_with_encoder(WebPEncoder::new_lossless(&mut webp))
+            .unwrap();
+        assert!(resized[0].bytes.len() < webp.len());
+    }
+}
+
+#[test]
+fn small_images_are_not_upscaled() {
+    let variants = process(&jpeg(200, 100)).unwrap();
+    assert!(variants.iter().all(|v| (v.width, v.height) == (200, 100)));
+}
+
+#[test]
+fn exif_is_applied_and_stripped() {
+    // Orientation 6：需要顺时针旋转 90 度
+    let upload = with_exif_orientation(&jpeg(400, 200), 6);
+    assert!(upload.windows(4).any(|w| w == b"Exif"));
+
+    let processed = imaging::process(
+        &upload,
+        &ImageSettings::default(),
+        &UploadSettings::default(),
+    )
+    .unwrap();
+    assert_eq!(processed.mime, "image/jpeg");
+    assert_eq!((processed.width, processed.height), (200, 400));
+    let variants = processed.variants;
+
+    let original = variants
+        .iter()
+        .find(|v| v.name == "original" && v.format == OutputFormat::Jpeg)
+        .unwrap();
+    assert_eq!((original.width, original.height), (200, 400));
+    let decoded = image::load(Cursor::new(&original.bytes), ImageFormat::Jpeg).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (200, 400));
+    assert!(variants
+        .iter()
+        .all(|v| !v.bytes.windows(4).any(|w| w == b"Exif")));
+}
+
+#[test]
+fn garbage_is_rejected() {
+    let err = process(b"definitely not an image").unwrap_err();
+    assert!(matches!(err, ImagingError::UnsupportedType(_)));
+
+    let mut truncated = jpeg(100, 100);
+    truncated.truncate(40);
+    assert!(matches!(process(&truncated), Err(ImagingError::Decode(_))));
+}
+
+#[test]
+fn content_type_is_sniffed_from_magic_bytes() {
+    let png_only = UploadSettings {
+        allowed_types: vec!["image/png".to_string()],
+        ..Default::default()
+    };
+    let err = imaging::process(&jpeg(10, 10), &ImageSettings::default(), &png_only).unwrap_err();
+    assert!(matches!(err, ImagingError::UnsupportedType(ref t) if t == "image/jpeg"));
+
+    let bmp = imaging::sniff(
+        b"BM\0\0\0\0\0\0\0\0",
+        &UploadSettings::default().allowed_types,
+    );
+    assert!(matches!(bmp, Err(ImagingError::UnsupportedType(ref t)) if t == "image/bmp"));
+}
+
+#[test]
+fn oversized_dimensions_are_rejected_before_decoding() {
+    let limits = UploadSettings {
+        max_pixels: 10_000,
+        ..Default::default()
+    };
+    let err = imaging::process(&jpeg(400, 200), &ImageSettings::default(), &limits).unwrap_err();
+    assert!(matches!(
+        err,
+        ImagingError::TooManyPixels {
+            width: 400,
+            height: 200
+        }
+    ));
+}
+
+#[test]
+fn object_keys_are_predictable() {
+    let user = "6f1c2a4e-0000-4000-8000-000000000001".parse().unwrap();
+    let image = "6f1c2a4e-0000-4000-8000-000000000002".parse().unwrap();
+    assert_eq!(
+        imaging::object_key(user, image, "thumb", OutputFormat::Webp),
+        "images/6f1c2a4e-0000-4000-8000-000000000001/6f1c2a4e-0000-4000-8000-000000000002/thumb.webp"
+    );
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use api::{config::AppConfig, object_store::LocalStore, wechat::WechatConfig};
+use axum::{extract::Query, routing::get, Json, Router};
+use chrono::Utc;
+use common::{serve, user_model, wechat_config};
+use entity::{refresh_tokens, users};
+use serde_json::{json, Value};
+use service::sea_orm::{
+    prelude::DateTimeWithTimeZone, sqlx::types::uuid, DatabaseBackend, DatabaseConnection,
+    MockDatabase,
+};
+
+/// 启动一个模拟微信 jscode2session 的本地服务：
+/// `good` 返回 openid，`busy` 第一次返回系统繁忙，其余 js_code 返回 40029
+async fn spawn_wechat_stub() -> String {
+    let busy_calls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new().route(
+        "/sns/jscode2session",
+        get(move |Query(params): Query<HashMap<String, String>>| {
+            let busy_calls = busy_calls.clone();
+            async move {
+                match params.get("js_code").map(String::as_str) {
+                    Some("good") => Json(json!({ "openid": "openid-1", "session_key": "sk" })),
+                    Some("busy") if busy_calls.fetch_add(1, Ordering::SeqCst) == 0 => {
+                        Json(json!({ "errcode": -1, "errmsg": "system busy" }))
+                    }
+                    Some("busy") => Json(json!({ "openid": "openid-1", "session_key": "sk" })),
+                    _ => Json(json!({ "errcode": 40029, "errmsg": "invalid code" })),
+                }
+            }
+        }),
+    );
+    serve(app).await
+}
+
+/// 已存在用户登录时依次查询用户、写入 refresh token
+fn login_db(user: &users::Model) -> DatabaseConnection {
+    let now = DateTimeWithTimeZone::from(Utc::now());
+    MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![refresh_tokens::Model {
+            id: uuid::Uuid::new_v4(),
+            uid: user.id,
+            replaced_by: None,
+            expires_at: now + chrono::Duration::days(30),
+            revoked_at: None,
+            create_time: now,
+        }]])
+        .into_connection()
+}
+
+async fn spawn_app(conn: DatabaseConnection, wechat_base_url: String) -> String {
+    // 登录流程不访问对象存储
+    let store = LocalStore::new(std::env::temp_dir().join("leafstore-login"), "/uploads");
+    let wechat = WechatConfig {
+        base_url: wechat_base_url,
+        timeout: Duration::from_secs(2),
+        retries: 1,
+        ..wechat_config()
+    };
+    common::spawn_app(conn, store, AppConfig::default(), wechat).await
+}
+
+async fn login(app_url: &str, js_code: &str) -> (u16, Value) {
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/login"))
+        .json(&json!({ "js_code": js_code }))
+        .send()
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    let body = resp.text().await.unwrap();
+    (
+        status,
+        serde_json::from_str(&body).unwrap_or(Value::String(body)),
+    )
+}
+
+#[tokio::test]
+async fn login_existing_user_returns_token_pair() {
+    let user = user_model();
+    let app_url = spawn_app(login_db(&user), spawn_wechat_stub().await).await;
+
+    let (status, body) = login(&app_url, "good").await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["user"]["id"], json!(user.id));
+    assert_eq!(body["data"]["session_key"], "sk");
+    assert!(body["data"]["token"].is_string());
+    assert!(body["data"]["refresh_token"].is_string());
+}
+
+#[tokio::test]
+async fn login_retries_when_wechat_is_busy() {
+    let user = user_model();
+    let app_url = spawn_app(login_db(&user), spawn_wechat_stub().await).await;
+
+    let (status, body) = login(&app_url, "busy").await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["user"]["app_id"], "openid-1");
+}
+
+#[tokio::test]
+async fn login_with_invalid_code_is_rejected() {
+    let conn = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app_url = spawn_app(conn, spawn_wechat_stub().await).await;
+
+    let (status, body) = login(&app_url, "expired").await;
+
+    assert_eq!(status, 502);
+    assert_eq!(body["status"], "Error");
+    assert_eq!(body["data"]["error_code"], "UPSTREAM_ERROR");
+}
+
+#[tokio::test]
+async fn invalid_login_payload_returns_field_errors() {
+    let conn = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app_url = spawn_app(conn, spawn_wechat_stub().await).await;
+
+    let (status, body) = login(&app_url, "").await;
+
+    assert_eq!(status, 422);
+    assert_eq!(body["data"]["error_code"], "VALIDATION_FAILED");
+    assert!(body["data"]["fields"]["js_code"].is_array());
+}
+
+#[tokio::test]
+async fn malformed_json_is_rejected() {
+    let conn = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app_url = spawn_app(conn, spawn_wechat_stub().await).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/login"))
+        .header("content-type", "application/json")
+        .body("{")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["error_code"], "BAD_REQUEST");
+}
+
+#[tokio::test]
+async fn missing_token_returns_json_error() {
+    let conn = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app_url = spawn_app(conn, spawn_wechat_stub().await).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/logout"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 401);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], 401);
+    assert_eq!(body["data"]["error_code"], "UNAUTHORIZED");
+}
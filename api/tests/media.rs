@@ -0,0 +1,129 @@
+mod common;
+
+use api::{config::AppConfig, object_store::MinioStore};
+use chrono::Utc;
+use common::{access_token, user_model, wechat_config};
+use entity::{storage_usage, users};
+use minio::s3::{creds::StaticProvider, http::BaseUrl, ClientBuilder};
+use serde_json::{json, Value};
+use service::sea_orm::{
+    prelude::DateTimeWithTimeZone, DatabaseBackend, DatabaseConnection, MockDatabase,
+};
+
+/// 启动应用并返回地址和已登录用户的 access token
+/// MinIO 客户端指定了 region，预签名时不访问网络
+async fn spawn_app(conn: DatabaseConnection, user: &users::Model) -> (String, String) {
+    let mut base_url = "http://localhost:9000/".parse::<BaseUrl>().unwrap();
+    base_url.region = "us-east-1".to_string();
+    let client = ClientBuilder::new(base_url.clone())
+        .provider(Some(Box::new(StaticProvider::new(
+            "access-key",
+            "secret-key",
+            None,
+        ))))
+        .build()
+        .unwrap();
+    let store = MinioStore::new(
+        client,
+        base_url,
+        "collection",
+        "http://localhost:9000/collection",
+    );
+    let app_url = common::spawn_app(conn, store, AppConfig::default(), wechat_config()).await;
+    (app_url, access_token(user))
+}
+
+async fn presign(conn: DatabaseConnection, user: &users::Model, body: Value) -> (u16, Value) {
+    let (app_url, token) = spawn_app(conn, user).await;
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/media/presign"))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn presigned_upload_targets_the_users_staging_area() {
+    let user = user_model();
+    // 鉴权查询用户，再查询存储用量（尚无记录）
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([Vec::<storage_usage::Model>::new()])
+        .into_connection();
+
+    let (status, body) = presign(
+        conn,
+        &user,
+        json!({ "content_type": "image/jpeg", "size": 1024 }),
+    )
+    .await;
+
+    assert_eq!(status, 200);
+    let data = &body["data"];
+    let upload_id = data["upload_id"].as_str().unwrap();
+    assert_eq!(data["url"], "http://localhost:9000/collection");
+    assert_eq!(data["method"], "POST");
+    // 表单中的 key 和 Content-Type 都由签名的策略限定，客户端不能修改
+    let fields = &data["fields"];
+    assert_eq!(fields["key"], format!("uploads/{}/{}", user.id, upload_id));
+    assert_eq!(fields["Content-Type"], "image/jpeg");
+    assert!(fields["policy"].is_string());
+    assert!(fields["x-amz-signature"].is_string());
+    assert_eq!(data["expires_in"], 900);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn presign_rejects_disallowed_type_and_size() {
+    let user = user_model();
+    let auth_only = || {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user.clone()]])
+            .into_connection()
+    };
+
+    let (status, body) = presign(
+        auth_only(),
+        &user,
+        json!({ "content_type": "image/svg+xml", "size": 1024 }),
+    )
+    .await;
+    assert_eq!(status, 415);
+    assert_eq!(body["data"]["error_code"], "UNSUPPORTED_MEDIA_TYPE");
+
+    let (status, body) = presign(
+        auth_only(),
+        &user,
+        json!({ "content_type": "image/png", "size": 100 * 1024 * 1024 }),
+    )
+    .await;
+    assert_eq!(status, 413);
+    assert_eq!(body["data"]["error_code"], "PAYLOAD_TOO_LARGE");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn presign_rejects_uploads_beyond_quota() {
+    let user = user_model();
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![storage_usage::Model {
+            uid: user.id,
+            used_bytes: 1000,
+            object_count: 6,
+            quota_bytes: Some(1024),
+            update_time: DateTimeWithTimeZone::from(Utc::now()),
+        }]])
+        .into_connection();
+
+    let (status, body) = presign(
+        conn,
+        &user,
+        json!({ "content_type": "image/png", "size": 100 }),
+    )
+    .await;
+
+    assert_eq!(status, 413);
+    assert_eq!(body["data"]["error_code"], "QUOTA_EXCEEDED");
+}
@@ -0,0 +1,98 @@
+use std::{fs, path::PathBuf};
+
+use api::object_store::{LocalStore, ObjectMeta, ObjectStore, StorageError};
+use sha2::{Digest, Sha256};
+
+fn store_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("leafstore-store-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[tokio::test]
+async fn local_store_round_trip() {
+    let root = store_dir("round-trip");
+    let store = LocalStore::new(&root, "/uploads/");
+    store.init().await.unwrap();
+
+    let key = "images/user/image/thumb.webp";
+    store
+        .put(key, b"webp bytes".to_vec(), "image/webp")
+        .await
+        .unwrap();
+
+    assert_eq!(fs::read(root.join(key)).unwrap(), b"webp bytes");
+    assert_eq!(store.get(key).await.unwrap(), b"webp bytes");
+    assert_eq!(store.stat(key).await.unwrap(), ObjectMeta { size: 10 });
+    assert_eq!(
+        store.sha256(key).await.unwrap(),
+        hex::encode(Sha256::digest(b"webp bytes"))
+    );
+    assert_eq!(
+        store.public_url(key),
+        "/uploads/images/user/image/thumb.webp"
+    );
+    // 只留下最终文件，不残留临时文件
+    assert_eq!(
+        fs::read_dir(root.join("images/user/image"))
+            .unwrap()
+            .count(),
+        1
+    );
+
+    store.delete(key).await.unwrap();
+    assert!(matches!(
+        store.get(key).await,
+        Err(StorageError::NotFound(_))
+    ));
+    assert!(matches!(
+        store.stat(key).await,
+        Err(StorageError::NotFound(_))
+    ));
+    assert!(matches!(
+        store.sha256(key).await,
+        Err(StorageError::NotFound(_))
+    ));
+    // 重复删除不报错
+    store.delete(key).await.unwrap();
+}
+
+#[tokio::test]
+async fn local_store_rejects_keys_outside_root() {
+    let root = store_dir("traversal");
+    let store = LocalStore::new(&root, "/uploads");
+
+    for key in [
+        "../escape",
+        "images/../../escape",
+        "/etc/passwd",
+        "images\\..\\escape",
+        "",
+    ] {
+        assert!(
+            matches!(
+                store.put(key, b"x".to_vec(), "image/png").await,
+                Err(StorageError::InvalidKey(_))
+            ),
+            "{key:?} should be rejected"
+        );
+    }
+    assert!(!root.parent().unwrap().join("escape").exists());
+}
+
+#[tokio::test]
+async fn local_store_has_no_presigned_uploads() {
+    let store = LocalStore::new(store_dir("presign"), "/uploads");
+
+    assert!(matches!(
+        store
+            .presign_post("uploads/user/id", "image/jpeg", 1024, 900)
+            .await,
+        Err(StorageError::Unsupported(_))
+    ));
+    // 本地目录没有带时效的签名地址，私有对象不能退化成公开地址
+    assert!(matches!(
+        store.presign_get("images/user/id/original.jpg", 900).await,
+        Err(StorageError::Unsupported(_))
+    ));
+}
@@ -0,0 +1,50 @@
+use api::middleware::{auth::Claims, revocation::RevocationList};
+use chrono::{Duration, Utc};
+use entity::token_revocations;
+use service::{
+    sea_orm::{prelude::DateTimeWithTimeZone, sqlx::types::uuid, DatabaseBackend, MockDatabase},
+    user::Role,
+};
+
+fn claims_issued_at(at: chrono::DateTime<Utc>) -> Claims {
+    Claims {
+        exp: (at + Duration::minutes(15)).timestamp() as usize,
+        iat: at.timestamp() as usize,
+        iat_ms: at.timestamp_millis(),
+        email: "openid-1".to_string(),
+        jti: uuid::Uuid::new_v4(),
+        role: Role::User,
+    }
+}
+
+#[tokio::test]
+async fn login_in_same_second_as_revoke_is_accepted() {
+    let uid = uuid::Uuid::new_v4();
+    // 吊销发生在某一秒的中间，前后各签发一个同一秒内的 token
+    let second = Utc::now().timestamp();
+    let revoked_at = chrono::DateTime::from_timestamp(second, 500_000_000).unwrap();
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![token_revocations::Model {
+            id: uuid::Uuid::new_v4(),
+            uid,
+            jti: None,
+            expires_at: DateTimeWithTimeZone::from(revoked_at + Duration::days(30)),
+            create_time: DateTimeWithTimeZone::from(revoked_at),
+        }]])
+        .into_connection();
+
+    let list = RevocationList::default();
+    list.revoke_user(
+        &db,
+        uid,
+        DateTimeWithTimeZone::from(revoked_at + Duration::days(30)),
+    )
+    .await
+    .unwrap();
+
+    let before = claims_issued_at(revoked_at - Duration::milliseconds(200));
+    let after = claims_issued_at(revoked_at + Duration::milliseconds(200));
+    assert_eq!(before.iat, after.iat);
+    assert!(list.is_user_revoked(&uid, before.issued_at_ms()));
+    assert!(!list.is_user_revoked(&uid, after.issued_at_ms()));
+}
@@ -0,0 +1,335 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use api::{
+    config::AppConfig,
+    object_store::{LocalStore, ObjectStore},
+};
+use chrono::Utc;
+use common::{access_token, user_model, wechat_config};
+use entity::{media, upload_parts, upload_sessions, users};
+use serde_json::{json, Value};
+use service::sea_orm::{
+    prelude::DateTimeWithTimeZone, sqlx::types::uuid::Uuid, DatabaseBackend, DatabaseConnection,
+    MockDatabase, MockExecResult,
+};
+
+/// 测试中使用很小的分片，S3 的 5MB 下限只在加载配置时检查
+const PART_BYTES: usize = 16;
+
+fn exec(rows_affected: u64) -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected,
+    }
+}
+
+fn local_store(name: &str) -> (PathBuf, LocalStore) {
+    let root =
+        std::env::temp_dir().join(format!("leafstore-uploads-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let store = LocalStore::new(&root, "http://127.0.0.1:3001/uploads");
+    let _ = fs::remove_dir_all(store.multipart_root());
+    (root, store)
+}
+
+/// 一段 20 字节的 MP4：偏移 4 处为 ftyp box，按 16 字节分为两片
+fn mp4_bytes() -> Vec<u8> {
+    let mut bytes = b"\0\0\0\x14ftypisom\0\0\0\0".to_vec();
+    bytes.extend_from_slice(b"mdat");
+    bytes
+}
+
+/// 启动应用并返回地址和已登录用户的 access token
+async fn spawn_app(
+    conn: DatabaseConnection,
+    store: LocalStore,
+    user: &users::Model,
+) -> (String, String) {
+    let mut config = AppConfig::default();
+    config.upload.part_bytes = PART_BYTES;
+    let app_url = common::spawn_app(conn, store, config, wechat_config()).await;
+    (app_url, access_token(user))
+}
+
+/// 在对象存储中创建分片上传，返回对应的视频会话
+async fn video_session(
+    store: &LocalStore,
+    user: &users::Model,
+    size: i64,
+) -> upload_sessions::Model {
+    let id = Uuid::new_v4();
+    let object_key = format!("videos/{}/{}/original.mp4", user.id, id);
+    let storage_upload_id = store
+        .create_multipart(&object_key, "video/mp4")
+        .await
+        .unwrap();
+    let now = DateTimeWithTimeZone::from(Utc::now());
+    upload_sessions::Model {
+        id,
+        owner: user.id,
+        object_key,
+        storage_upload_id,
+        content_type: "video/mp4".to_string(),
+        size,
+        part_size: PART_BYTES as i64,
+        status: "uploading".to_string(),
+        media_id: None,
+        create_time: now,
+        update_time: now,
+    }
+}
+
+fn part(session: &upload_sessions::Model, number: i32, size: i64) -> upload_parts::Model {
+    upload_parts::Model {
+        upload_id: session.id,
+        number,
+        etag: format!("etag-{number}"),
+        size,
+        sha256: format!("sha-{number}"),
+        create_time: DateTimeWithTimeZone::from(Utc::now()),
+    }
+}
+
+async fn put_part(
+    app_url: &str,
+    token: &str,
+    id: Uuid,
+    number: i32,
+    body: Vec<u8>,
+) -> (u16, Value) {
+    let resp = reqwest::Client::new()
+        .put(format!("{app_url}/api/uploads/{id}/parts/{number}"))
+        .bearer_auth(token)
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    (resp.status().as_u16(), resp.json().await.unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn init_reserves_quota_and_reports_parts() {
+    let user = user_model();
+    let (root, store) = local_store("init");
+    let multipart_root = store.multipart_root().to_path_buf();
+    let session = video_session(&store, &user, 40).await;
+    // 鉴权查询用户，预占配额（插入用量行、累加用量），再写入会话
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_exec_results([exec(1), exec(1)])
+        .append_query_results([vec![session.clone()]])
+        .into_connection();
+    let (app_url, token) = spawn_app(conn, store, &user).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/uploads"))
+        .bearer_auth(&token)
+        .json(&json!({ "content_type": "video/mp4", "size": 40 }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: Value = resp.json().await.unwrap();
+    let data = &body["data"];
+    assert_eq!(data["upload_id"], json!(session.id));
+    assert_eq!(data["part_size"], 16);
+    assert_eq!(data["total_parts"], 3);
+    assert_eq!(data["uploaded_parts"], json!([]));
+    assert_eq!(data["status"], "uploading");
+    // 会话本身和服务端新建的分片上传各占一个目录，都不在对外提供的 root 下
+    assert_eq!(fs::read_dir(multipart_root).unwrap().count(), 2);
+    assert!(!root.exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn init_rejects_unsupported_types_and_oversized_videos() {
+    let user = user_model();
+    let auth_only = || {
+        MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![user.clone()]])
+            .into_connection()
+    };
+    let init = |body: Value| {
+        let (_, store) = local_store("reject");
+        let conn = auth_only();
+        let user = user.clone();
+        async move {
+            let (app_url, token) = spawn_app(conn, store, &user).await;
+            let resp = reqwest::Client::new()
+                .post(format!("{app_url}/api/uploads"))
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            let status = resp.status().as_u16();
+            let body: Value = resp.json().await.unwrap();
+            (status, body)
+        }
+    };
+
+    let (status, body) = init(json!({ "content_type": "application/zip", "size": 10 })).await;
+    assert_eq!(status, 415);
+    assert_eq!(body["data"]["error_code"], "UNSUPPORTED_MEDIA_TYPE");
+
+    let (status, body) =
+        init(json!({ "content_type": "video/mp4", "size": 1024 * 1024 * 1024 })).await;
+    assert_eq!(status, 413);
+    assert_eq!(body["data"]["error_code"], "PAYLOAD_TOO_LARGE");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn parts_must_match_the_expected_size_and_content() {
+    let user = user_model();
+    let (_, store) = local_store("parts");
+    let session = video_session(&store, &user, 20).await;
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .into_connection();
+    let (app_url, token) = spawn_app(conn, store, &user).await;
+
+    // 最后一片只能是剩余的 4 字节
+    let (status, _) = put_part(&app_url, &token, session.id, 2, vec![0; 16]).await;
+    assert_eq!(status, 400);
+    // 分片编号超出范围
+    let (status, _) = put_part(&app_url, &token, session.id, 3, vec![0; 4]).await;
+    assert_eq!(status, 400);
+    // 第一片的文件头不是视频
+    let (status, body) = put_part(&app_url, &token, session.id, 1, vec![0; 16]).await;
+    assert_eq!(status, 415);
+    assert_eq!(body["data"]["error_code"], "UNSUPPORTED_MEDIA_TYPE");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn uploaded_parts_complete_into_a_video() {
+    let user = user_model();
+    let (root, store) = local_store("complete");
+    let session = video_session(&store, &user, 20).await;
+    let bytes = mp4_bytes();
+    let parts = [part(&session, 1, 16), part(&session, 2, 4)];
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        // 第一片：鉴权、查询会话、记录分片、列出分片
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_exec_results([exec(1)])
+        .append_query_results([vec![parts[0].clone()]])
+        // 第二片
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_exec_results([exec(1)])
+        .append_query_results([parts.to_vec()])
+        // complete：鉴权、查询会话、列出分片、按哈希查重、写入 media、标记完成并删除分片记录
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_query_results([parts.to_vec()])
+        .append_query_results([Vec::<media::Model>::new()])
+        .append_exec_results([exec(1), exec(1), exec(2)])
+        .into_connection();
+    let (app_url, token) = spawn_app(conn, store, &user).await;
+
+    let (status, body) = put_part(&app_url, &token, session.id, 1, bytes[..16].to_vec()).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["uploaded_parts"], json!([1]));
+    assert_eq!(body["data"]["uploaded_bytes"], 16);
+    let (status, body) = put_part(&app_url, &token, session.id, 2, bytes[16..].to_vec()).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["uploaded_parts"], json!([1, 2]));
+
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/uploads/{}/complete", session.id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["data"]["id"], json!(session.id));
+    assert_eq!(
+        body["data"]["url"],
+        format!("http://127.0.0.1:3001/uploads/{}", session.object_key)
+    );
+    assert_eq!(fs::read(root.join(&session.object_key)).unwrap(), bytes);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn complete_requires_every_part() {
+    let user = user_model();
+    let (_, store) = local_store("missing");
+    let session = video_session(&store, &user, 40).await;
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_query_results([vec![part(&session, 2, 16)]])
+        .into_connection();
+    let (app_url, token) = spawn_app(conn, store, &user).await;
+
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/uploads/{}/complete", session.id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 400);
+    let body: Value = resp.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().contains("[1, 3]"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn other_users_uploads_are_not_found() {
+    let user = user_model();
+    let (_, store) = local_store("foreign");
+    let mut session = video_session(&store, &user, 20).await;
+    session.owner = Uuid::new_v4();
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .into_connection();
+    let (app_url, token) = spawn_app(conn, store, &user).await;
+
+    let resp = reqwest::Client::new()
+        .get(format!("{app_url}/api/uploads/{}", session.id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status().as_u16(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn expired_sessions_reject_parts() {
+    let user = user_model();
+    let (_, store) = local_store("expired");
+    let mut session = video_session(&store, &user, 20).await;
+    // 默认 session_ttl_secs 为 24 小时
+    session.create_time = DateTimeWithTimeZone::from(Utc::now() - chrono::Duration::days(2));
+    let conn = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .append_query_results([vec![user.clone()]])
+        .append_query_results([vec![session.clone()]])
+        .into_connection();
+    let (app_url, token) = spawn_app(conn, store, &user).await;
+
+    let (status, body) =
+        put_part(&app_url, &token, session.id, 1, mp4_bytes()[..16].to_vec()).await;
+    assert_eq!(status, 410);
+    assert_eq!(body["data"]["error_code"], "GONE");
+
+    let resp = reqwest::Client::new()
+        .post(format!("{app_url}/api/uploads/{}/complete", session.id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 410);
+}
@@ -2,6 +2,13 @@
 
 pub mod prelude;
 
-pub mod users;
+pub mod block_revisions;
 pub mod blocks;
+pub mod media;
+pub mod refresh_tokens;
 pub mod search_history;
+pub mod storage_usage;
+pub mod token_revocations;
+pub mod upload_parts;
+pub mod upload_sessions;
+pub mod users;
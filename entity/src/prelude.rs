@@ -1,5 +1,12 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.16
 
+pub use super::block_revisions::Entity as BlockRevisions;
 pub use super::blocks::Entity as Blocks;
+pub use super::media::Entity as Media;
+pub use super::refresh_tokens::Entity as RefreshTokens;
 pub use super::search_history::Entity as SearchHistory;
+pub use super::storage_usage::Entity as StorageUsage;
+pub use super::token_revocations::Entity as TokenRevocations;
+pub use super::upload_parts::Entity as UploadParts;
+pub use super::upload_sessions::Entity as UploadSessions;
 pub use super::users::Entity as Users;
@@ -8,10 +8,11 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
-    pub uid: Option<Uuid>,
-    pub history: Option<Json>,
+    pub uid: Uuid,
+    pub query: String,
+    pub hits: i32,
+    pub last_used_at: DateTimeWithTimeZone,
     pub create_time: DateTimeWithTimeZone,
-    pub update_time: DateTimeWithTimeZone,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
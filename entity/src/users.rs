@@ -14,6 +14,7 @@ pub struct Model {
     pub phone: Option<String>,
     pub email: Option<String>,
     pub app_id: String,
+    pub role: String,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
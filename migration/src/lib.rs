@@ -1,12 +1,40 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20261017_000001_create_refresh_tokens;
+mod m20261017_000002_create_token_revocations;
+mod m20261017_000003_add_role_to_users;
+mod m20261017_000004_add_visibility_to_blocks;
+mod m20261017_000005_split_block_coordinates;
+mod m20261017_000006_add_block_search;
+mod m20261017_000007_normalize_search_history;
+mod m20261017_000008_create_storage_usage;
+mod m20261017_000009_create_media;
+mod m20261017_000010_create_upload_sessions;
+mod m20261017_000011_create_block_revisions;
+mod m20261017_000012_add_deleted_at_to_blocks;
+mod m20261017_000013_add_status_to_blocks;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261017_000001_create_refresh_tokens::Migration),
+            Box::new(m20261017_000002_create_token_revocations::Migration),
+            Box::new(m20261017_000003_add_role_to_users::Migration),
+            Box::new(m20261017_000004_add_visibility_to_blocks::Migration),
+            Box::new(m20261017_000005_split_block_coordinates::Migration),
+            Box::new(m20261017_000006_add_block_search::Migration),
+            Box::new(m20261017_000007_normalize_search_history::Migration),
+            Box::new(m20261017_000008_create_storage_usage::Migration),
+            Box::new(m20261017_000009_create_media::Migration),
+            Box::new(m20261017_000010_create_upload_sessions::Migration),
+            Box::new(m20261017_000011_create_block_revisions::Migration),
+            Box::new(m20261017_000012_add_deleted_at_to_blocks::Migration),
+            Box::new(m20261017_000013_add_status_to_blocks::Migration),
+        ]
     }
 }
@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 创建TokenRevocations表
+        // jti 为空表示吊销该用户在 create_time 之前签发的全部 token
+        manager
+            .create_table(
+                Table::create()
+                    .table(TokenRevocations::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(TokenRevocations::Id))
+                    .col(ColumnDef::new(TokenRevocations::Uid).uuid().not_null())
+                    .col(ColumnDef::new(TokenRevocations::Jti).uuid())
+                    .col(
+                        ColumnDef::new(TokenRevocations::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TokenRevocations::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_token_revocations_expires_at")
+                    .table(TokenRevocations::Table)
+                    .col(TokenRevocations::ExpiresAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TokenRevocations::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum TokenRevocations {
+    Table,
+    Id,
+    Uid,
+    Jti,
+    ExpiresAt,
+    CreateTime,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 已发布 block 的可见性：public 所有登录用户可见，private 仅作者可见
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .add_column(
+                        ColumnDef::new(Blocks::Visibility)
+                            .string()
+                            .not_null()
+                            .default("private"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 新建的 block 默认仅作者可见；迁移前已发布的 block 原本所有人可见，保持公开
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE blocks SET visibility = 'public' WHERE draft IS NOT TRUE")
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_blocks_pid")
+                    .table(Blocks::Table)
+                    .col(Blocks::Pid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_blocks_pid")
+                    .table(Blocks::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .drop_column(Blocks::Visibility)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Blocks {
+    Table,
+    Pid,
+    Visibility,
+}
@@ -0,0 +1,102 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 将 latitude_and_longitude 字符串拆分为数值类型的经纬度列
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .add_column(ColumnDef::new(Blocks::Latitude).double().null())
+                    .add_column(ColumnDef::new(Blocks::Longitude).double().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 迁移已有数据：只解析 `纬度,经度` 格式且在合法范围内的值，其余置空
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            r"UPDATE blocks
+              SET latitude = trim(split_part(latitude_and_longitude, ',', 1))::double precision,
+                  longitude = trim(split_part(latitude_and_longitude, ',', 2))::double precision
+              WHERE latitude_and_longitude ~ '^\s*[-+]?[0-9]+(\.[0-9]+)?\s*,\s*[-+]?[0-9]+(\.[0-9]+)?\s*$'",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE blocks SET latitude = NULL, longitude = NULL
+             WHERE latitude NOT BETWEEN -90 AND 90 OR longitude NOT BETWEEN -180 AND 180",
+        )
+        .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .drop_column(Blocks::LatitudeAndLongitude)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 附近查询先按经纬度范围过滤
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_blocks_latitude_longitude")
+                    .table(Blocks::Table)
+                    .col(Blocks::Latitude)
+                    .col(Blocks::Longitude)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_blocks_latitude_longitude")
+                    .table(Blocks::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .add_column(ColumnDef::new(Blocks::LatitudeAndLongitude).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE blocks SET latitude_and_longitude = latitude::text || ',' || longitude::text
+                 WHERE latitude IS NOT NULL AND longitude IS NOT NULL",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .drop_column(Blocks::Latitude)
+                    .drop_column(Blocks::Longitude)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Blocks {
+    Table,
+    LatitudeAndLongitude,
+    Latitude,
+    Longitude,
+}
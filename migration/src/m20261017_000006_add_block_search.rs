@@ -0,0 +1,55 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // pg_trgm 用于中文等无空格分词文本的子串匹配和相似度排序
+        db.execute_unprepared("CREATE EXTENSION IF NOT EXISTS pg_trgm")
+            .await?;
+
+        // 全文检索向量，使用 simple 配置避免对英文以外的文本做错误的词干处理
+        db.execute_unprepared(
+            "ALTER TABLE blocks ADD COLUMN search_vector tsvector
+             GENERATED ALWAYS AS (
+                 setweight(to_tsvector('simple', coalesce(context, '')), 'A') ||
+                 setweight(to_tsvector('simple', coalesce(location, '')), 'B')
+             ) STORED",
+        )
+        .await?;
+
+        db.execute_unprepared(
+            "CREATE INDEX idx_blocks_search_vector ON blocks USING GIN (search_vector)",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE INDEX idx_blocks_context_trgm ON blocks USING GIN (context gin_trgm_ops)",
+        )
+        .await?;
+        db.execute_unprepared(
+            "CREATE INDEX idx_blocks_location_trgm ON blocks USING GIN (location gin_trgm_ops)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_blocks_location_trgm")
+            .await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_blocks_context_trgm")
+            .await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_blocks_search_vector")
+            .await?;
+        db.execute_unprepared("ALTER TABLE blocks DROP COLUMN IF EXISTS search_vector")
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 原表每次搜索插入一行 JSON，改为每个 (用户, 搜索词) 一行
+        manager
+            .rename_table(
+                Table::rename()
+                    .table(SearchHistory::Table, SearchHistoryLegacy::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SearchHistory::Table)
+                    .col(
+                        ColumnDef::new(SearchHistory::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SearchHistory::Uid).uuid().not_null())
+                    .col(
+                        ColumnDef::new(SearchHistory::Query)
+                            .string_len(100)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SearchHistory::Hits)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .col(
+                        ColumnDef::new(SearchHistory::LastUsedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SearchHistory::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_search_history_uid_query")
+                    .table(SearchHistory::Table)
+                    .col(SearchHistory::Uid)
+                    .col(SearchHistory::Query)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_search_history_uid_last_used_at")
+                    .table(SearchHistory::Table)
+                    .col(SearchHistory::Uid)
+                    .col(SearchHistory::LastUsedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        // 前缀联想查询 `query LIKE 'prefix%'`
+        db.execute_unprepared(
+            "CREATE INDEX idx_search_history_query_prefix ON search_history (lower(query) text_pattern_ops)",
+        )
+        .await?;
+
+        // 迁移已有数据：展开 JSON 数组，按 normalize_query 的规则合并连续空白，
+        // 同一用户的相同搜索词合并计数
+        db.execute_unprepared(
+            "INSERT INTO search_history (id, uid, query, hits, last_used_at, create_time)
+             SELECT gen_random_uuid(), q.uid, q.query, count(*), max(q.update_time), min(q.create_time)
+             FROM (
+                 SELECT h.uid, h.create_time, h.update_time,
+                        rtrim(left(btrim(regexp_replace(e.value, '\\s+', ' ', 'g')), 100)) AS query
+                 FROM search_history_legacy h,
+                      jsonb_array_elements_text(
+                          CASE WHEN jsonb_typeof(h.history::jsonb) = 'array' THEN h.history::jsonb ELSE '[]'::jsonb END
+                      ) AS e(value)
+                 WHERE h.uid IS NOT NULL
+             ) q
+             WHERE q.query <> ''
+             GROUP BY q.uid, q.query",
+        )
+        .await?;
+
+        // 与 record_query 一致，每个用户只保留最近使用的 50 条
+        db.execute_unprepared(
+            "DELETE FROM search_history WHERE id IN (
+                 SELECT id FROM (
+                     SELECT id, row_number() OVER (PARTITION BY uid ORDER BY last_used_at DESC) AS rn
+                     FROM search_history
+                 ) ranked
+                 WHERE rn > 50
+             )",
+        )
+        .await?;
+
+        manager
+            .drop_table(Table::drop().table(SearchHistoryLegacy::Table).to_owned())
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SearchHistoryLegacy::Table)
+                    .col(
+                        ColumnDef::new(SearchHistory::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SearchHistory::Uid).uuid())
+                    .col(ColumnDef::new(SearchHistoryLegacy::History).json())
+                    .col(
+                        ColumnDef::new(SearchHistory::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(SearchHistoryLegacy::UpdateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 每个用户合并回一行，按最近使用时间排序
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO search_history_legacy (id, uid, history, create_time, update_time)
+                 SELECT gen_random_uuid(), uid, json_agg(query ORDER BY last_used_at DESC), min(create_time), max(last_used_at)
+                 FROM search_history
+                 GROUP BY uid",
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(SearchHistory::Table).to_owned())
+            .await?;
+        manager
+            .rename_table(
+                Table::rename()
+                    .table(SearchHistoryLegacy::Table, SearchHistory::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SearchHistory {
+    Table,
+    Id,
+    Uid,
+    Query,
+    Hits,
+    LastUsedAt,
+    CreateTime,
+}
+
+#[derive(DeriveIden)]
+enum SearchHistoryLegacy {
+    Table,
+    History,
+    UpdateTime,
+}
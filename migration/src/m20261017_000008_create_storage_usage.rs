@@ -0,0 +1,64 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 每个用户已占用的对象存储空间，上传时在同一条 UPDATE 中检查并累加，避免并发上传超出配额
+        // quota_bytes 为空时使用配置文件中的默认配额
+        manager
+            .create_table(
+                Table::create()
+                    .table(StorageUsage::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(StorageUsage::Uid)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(StorageUsage::UsedBytes)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(StorageUsage::ObjectCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(StorageUsage::QuotaBytes).big_integer())
+                    .col(
+                        ColumnDef::new(StorageUsage::UpdateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(StorageUsage::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum StorageUsage {
+    Table,
+    Uid,
+    UsedBytes,
+    ObjectCount,
+    QuotaBytes,
+    UpdateTime,
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 上传的图片，一行对应一次上传及其全部尺寸
+        // object_key 为原图首选格式的 key，variants 记录每个尺寸、格式的 key 和大小
+        manager
+            .create_table(
+                Table::create()
+                    .table(Media::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Media::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Media::Owner).uuid().not_null())
+                    .col(ColumnDef::new(Media::ObjectKey).string().not_null())
+                    .col(ColumnDef::new(Media::ContentHash).string_len(64).not_null())
+                    .col(ColumnDef::new(Media::Size).big_integer().not_null())
+                    .col(ColumnDef::new(Media::Mime).string().not_null())
+                    .col(ColumnDef::new(Media::Width).integer().not_null())
+                    .col(ColumnDef::new(Media::Height).integer().not_null())
+                    .col(ColumnDef::new(Media::Variants).json_binary().not_null())
+                    .col(
+                        ColumnDef::new(Media::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 同一用户重复上传相同内容时复用已有记录
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_media_owner_content_hash")
+                    .table(Media::Table)
+                    .col(Media::Owner)
+                    .col(Media::ContentHash)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Media::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Media {
+    Table,
+    Id,
+    Owner,
+    ObjectKey,
+    ContentHash,
+    Size,
+    Mime,
+    Width,
+    Height,
+    Variants,
+    CreateTime,
+}
@@ -0,0 +1,149 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 分片上传会话，对应对象存储中的一次 multipart upload
+        // status 为 uploading 或 completed，完成后 media_id 指向生成的 media，重复 complete 直接返回它
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadSessions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadSessions::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UploadSessions::Owner).uuid().not_null())
+                    .col(
+                        ColumnDef::new(UploadSessions::ObjectKey)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadSessions::StorageUploadId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadSessions::ContentType)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadSessions::Size)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadSessions::PartSize)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadSessions::Status)
+                            .string_len(16)
+                            .not_null()
+                            .default("uploading"),
+                    )
+                    .col(ColumnDef::new(UploadSessions::MediaId).uuid().null())
+                    .col(
+                        ColumnDef::new(UploadSessions::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadSessions::UpdateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 清理过期会话时按创建时间查找
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_upload_sessions_create_time")
+                    .table(UploadSessions::Table)
+                    .col(UploadSessions::CreateTime)
+                    .to_owned(),
+            )
+            .await?;
+
+        // 已上传的分片，同一编号重复上传时覆盖
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadParts::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(UploadParts::UploadId).uuid().not_null())
+                    .col(ColumnDef::new(UploadParts::Number).integer().not_null())
+                    .col(ColumnDef::new(UploadParts::Etag).string().not_null())
+                    .col(ColumnDef::new(UploadParts::Size).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(UploadParts::Sha256)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UploadParts::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(UploadParts::UploadId)
+                            .col(UploadParts::Number),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UploadParts::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UploadSessions::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadSessions {
+    Table,
+    Id,
+    Owner,
+    ObjectKey,
+    StorageUploadId,
+    ContentType,
+    Size,
+    PartSize,
+    Status,
+    MediaId,
+    CreateTime,
+    UpdateTime,
+}
+
+#[derive(DeriveIden)]
+enum UploadParts {
+    Table,
+    UploadId,
+    Number,
+    Etag,
+    Size,
+    Sha256,
+    CreateTime,
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // block 每次修改前的内容，revision 为该 block 内从 1 开始递增的版本号
+        // create_time 为这一版内容写入 block 的时间，即被覆盖前 block 的 update_time
+        manager
+            .create_table(
+                Table::create()
+                    .table(BlockRevisions::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(BlockRevisions::BlockId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(BlockRevisions::Revision)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BlockRevisions::Context).string().null())
+                    .col(ColumnDef::new(BlockRevisions::Imgs).json().null())
+                    .col(ColumnDef::new(BlockRevisions::Location).string().null())
+                    .col(ColumnDef::new(BlockRevisions::Latitude).double().null())
+                    .col(ColumnDef::new(BlockRevisions::Longitude).double().null())
+                    .col(ColumnDef::new(BlockRevisions::Draft).boolean().null())
+                    .col(
+                        ColumnDef::new(BlockRevisions::Visibility)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BlockRevisions::CreateTime)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(BlockRevisions::BlockId)
+                            .col(BlockRevisions::Revision),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BlockRevisions::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum BlockRevisions {
+    Table,
+    BlockId,
+    Revision,
+    Context,
+    Imgs,
+    Location,
+    Latitude,
+    Longitude,
+    Draft,
+    Visibility,
+    CreateTime,
+}
@@ -0,0 +1,86 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 发布状态：draft、scheduled、published、archived，draft 列保留给旧客户端，与 status 同步
+        // publish_at 为定时发布的时间，published_at 为第一次发布的时间
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .add_column(
+                        ColumnDef::new(Blocks::Status)
+                            .string()
+                            .not_null()
+                            .default("published"),
+                    )
+                    .add_column(
+                        ColumnDef::new(Blocks::PublishAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .add_column(
+                        ColumnDef::new(Blocks::PublishedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 迁移已有数据：draft = true 的为草稿，其余视为在创建时发布
+        let db = manager.get_connection();
+        db.execute_unprepared("UPDATE blocks SET status = 'draft' WHERE draft = true")
+            .await?;
+        db.execute_unprepared(
+            "UPDATE blocks SET published_at = create_time WHERE status = 'published'",
+        )
+        .await?;
+
+        // 定时发布任务按状态和发布时间查找到期的 block
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_blocks_status_publish_at")
+                    .table(Blocks::Table)
+                    .col(Blocks::Status)
+                    .col(Blocks::PublishAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_blocks_status_publish_at")
+                    .table(Blocks::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Blocks::Table)
+                    .drop_column(Blocks::Status)
+                    .drop_column(Blocks::PublishAt)
+                    .drop_column(Blocks::PublishedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Blocks {
+    Table,
+    Status,
+    PublishAt,
+    PublishedAt,
+}
@@ -1,86 +1,1123 @@
-use ::entity::{blocks, blocks::Entity as Block};
+use ::entity::{
+    block_revisions, block_revisions::Entity as BlockRevision, blocks, blocks::Entity as Block,
+};
 use chrono::Utc;
 use prelude::DateTimeWithTimeZone;
-use sea_orm::{sqlx::types::uuid, *};
+use sea_orm::{
+    sea_query::{Expr, Func},
+    sqlx::types::uuid,
+    *,
+};
 use serde::{Deserialize, Serialize};
 use serde_json;
+use validator::{Validate, ValidationError};
 
+use crate::{
+    error::ServiceError,
+    pagination::{Cursor, Page, SortOrder},
+    patch::{ensure_unmodified, Patch},
+    revision::RevisionServices,
+    validation::{parse_lat_lng, validate_image_urls, validate_lat_lng},
+};
 
-#[derive(Deserialize, Serialize, Debug)]
+/// 已发布 block 的可见性，草稿始终只有作者可见
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    #[default]
+    Private,
+}
+
+impl Visibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+/// block 的发布状态：草稿和定时发布只有作者可见，到达 publish_at 后由后台任务发布；归档后同样只有作者可见
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockStatus {
+    Draft,
+    Scheduled,
+    Published,
+    Archived,
+}
+
+impl BlockStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockStatus::Draft => "draft",
+            BlockStatus::Scheduled => "scheduled",
+            BlockStatus::Published => "published",
+            BlockStatus::Archived => "archived",
+        }
+    }
+
+    /// 旧客户端读取的 draft 字段
+    fn is_draft(&self) -> bool {
+        matches!(self, BlockStatus::Draft | BlockStatus::Scheduled)
+    }
+}
+
+/// 地球平均半径，单位米
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// 每纬度对应的距离，单位米
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+#[derive(Deserialize, Serialize, Debug, Default, Validate)]
+#[validate(
+    schema(function = "validate_coordinates"),
+    schema(function = "validate_schedule")
+)]
 pub struct BlockModel {
+    #[validate(length(max = 5000, message = "context must be at most 5000 characters"))]
     pub context: Option<String>,
+    #[validate(
+        length(max = 9, message = "at most 9 images are allowed"),
+        custom(function = "validate_image_urls")
+    )]
     pub imgs: Option<Vec<String>>,
+    #[validate(length(max = 200, message = "location must be at most 200 characters"))]
     pub location: Option<String>,
+    /// 兼容旧客户端的 `纬度,经度` 字符串，未提供 latitude/longitude 时使用
+    #[validate(custom(function = "validate_lat_lng"))]
     pub latitude_and_longitude: Option<String>,
+    #[validate(range(min = -90.0, max = 90.0, message = "latitude must be between -90 and 90"))]
+    pub latitude: Option<f64>,
+    #[validate(range(min = -180.0, max = 180.0, message = "longitude must be between -180 and 180"))]
+    pub longitude: Option<f64>,
+    /// 兼容旧客户端，未提供 status 时 true 为草稿、false 为发布
     pub draft: Option<bool>,
+    pub visibility: Option<Visibility>,
+    pub status: Option<BlockStatus>,
+    /// 定时发布的时间，只能与 scheduled 状态一起使用
+    pub publish_at: Option<DateTimeWithTimeZone>,
+}
+
+impl BlockModel {
+    /// 表单中的状态：优先使用 status，其次提供了 publish_at 时为定时发布，最后兼容旧的 draft
+    pub fn resolved_status(&self) -> Option<BlockStatus> {
+        self.status
+            .or_else(|| self.publish_at.map(|_| BlockStatus::Scheduled))
+            .or_else(|| {
+                self.draft.map(|draft| {
+                    if draft {
+                        BlockStatus::Draft
+                    } else {
+                        BlockStatus::Published
+                    }
+                })
+            })
+    }
+
+    /// 优先使用 latitude/longitude，其次解析旧的 latitude_and_longitude
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        match (self.latitude, self.longitude) {
+            (Some(lat), Some(lon)) => Some((lat, lon)),
+            _ => self
+                .latitude_and_longitude
+                .as_deref()
+                .and_then(parse_lat_lng),
+        }
+    }
+}
+
+/// latitude 和 longitude 必须同时提供
+fn validate_coordinates(form: &BlockModel) -> Result<(), ValidationError> {
+    if form.latitude.is_some() != form.longitude.is_some() {
+        return Err(ValidationError::new("coordinates")
+            .with_message("latitude and longitude must be provided together".into()));
+    }
+    Ok(())
+}
+
+fn validate_schedule(form: &BlockModel) -> Result<(), ValidationError> {
+    check_schedule(form.resolved_status(), form.publish_at)
+}
+
+/// 定时发布必须提供将来的 publish_at，其他状态不能提供
+fn check_schedule(
+    status: Option<BlockStatus>,
+    publish_at: Option<DateTimeWithTimeZone>,
+) -> Result<(), ValidationError> {
+    match (status, publish_at) {
+        (Some(BlockStatus::Scheduled), None) => Err(ValidationError::new("publish_at")
+            .with_message("publish_at is required for scheduled blocks".into())),
+        (Some(BlockStatus::Scheduled), Some(publish_at)) if publish_at <= Utc::now() => {
+            Err(ValidationError::new("publish_at")
+                .with_message("publish_at must be in the future".into()))
+        }
+        (Some(status), Some(_)) if status != BlockStatus::Scheduled => {
+            Err(ValidationError::new("publish_at")
+                .with_message("publish_at is only allowed for scheduled blocks".into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// PATCH 修改 block 时提交的内容：只修改提交的字段，可为空的字段提交 null 表示清空
+#[derive(Deserialize, Serialize, Debug, Default, Validate)]
+#[serde(default)]
+#[validate(
+    schema(function = "validate_patch_coordinates"),
+    schema(function = "validate_patch_schedule")
+)]
+pub struct BlockPatch {
+    #[validate(length(max = 5000, message = "context must be at most 5000 characters"))]
+    pub context: Patch<String>,
+    #[validate(
+        length(max = 9, message = "at most 9 images are allowed"),
+        custom(function = "validate_patch_image_urls")
+    )]
+    pub imgs: Patch<Vec<String>>,
+    #[validate(length(max = 200, message = "location must be at most 200 characters"))]
+    pub location: Patch<String>,
+    #[validate(range(min = -90.0, max = 90.0, message = "latitude must be between -90 and 90"))]
+    pub latitude: Patch<f64>,
+    #[validate(range(min = -180.0, max = 180.0, message = "longitude must be between -180 and 180"))]
+    pub longitude: Patch<f64>,
+    pub visibility: Option<Visibility>,
+    pub status: Option<BlockStatus>,
+    pub publish_at: Option<DateTimeWithTimeZone>,
+}
+
+impl BlockPatch {
+    /// 与 BlockModel 相同，只提交 publish_at 时为定时发布
+    pub fn resolved_status(&self) -> Option<BlockStatus> {
+        self.status
+            .or_else(|| self.publish_at.map(|_| BlockStatus::Scheduled))
+    }
+}
+
+fn validate_patch_image_urls(imgs: &Patch<Vec<String>>) -> Result<(), ValidationError> {
+    imgs.value()
+        .map_or(Ok(()), |imgs| validate_image_urls(imgs))
+}
+
+/// 经纬度必须同时提交、同时清空或同时省略
+fn validate_patch_coordinates(form: &BlockPatch) -> Result<(), ValidationError> {
+    if std::mem::discriminant(&form.latitude) != std::mem::discriminant(&form.longitude) {
+        return Err(ValidationError::new("coordinates")
+            .with_message("latitude and longitude must be provided together".into()));
+    }
+    Ok(())
+}
+
+fn validate_patch_schedule(form: &BlockPatch) -> Result<(), ValidationError> {
+    check_schedule(form.resolved_status(), form.publish_at)
+}
+
+/// 自动保存草稿时提交的内容，省略的字段保持不变
+#[derive(Deserialize, Serialize, Debug, Default, Validate)]
+#[validate(schema(function = "validate_autosave_coordinates"))]
+pub struct AutosaveModel {
+    #[validate(length(max = 5000, message = "context must be at most 5000 characters"))]
+    pub context: Option<String>,
+    #[validate(
+        length(max = 9, message = "at most 9 images are allowed"),
+        custom(function = "validate_image_urls")
+    )]
+    pub imgs: Option<Vec<String>>,
+    #[validate(length(max = 200, message = "location must be at most 200 characters"))]
+    pub location: Option<String>,
+    #[validate(range(min = -90.0, max = 90.0, message = "latitude must be between -90 and 90"))]
+    pub latitude: Option<f64>,
+    #[validate(range(min = -180.0, max = 180.0, message = "longitude must be between -180 and 180"))]
+    pub longitude: Option<f64>,
+}
+
+fn validate_autosave_coordinates(form: &AutosaveModel) -> Result<(), ValidationError> {
+    if form.latitude.is_some() != form.longitude.is_some() {
+        return Err(ValidationError::new("coordinates")
+            .with_message("latitude and longitude must be provided together".into()));
+    }
+    Ok(())
+}
+
+/// block 列表的过滤条件，省略的条件不过滤
+#[derive(Debug, Default, Clone)]
+pub struct BlockQuery {
+    /// 只列出某个用户的 block
+    pub owner: Option<uuid::Uuid>,
+    pub status: Option<BlockStatus>,
+    /// 创建时间范围，包含 from，不包含 to
+    pub from: Option<DateTimeWithTimeZone>,
+    pub to: Option<DateTimeWithTimeZone>,
+    pub has_images: Option<bool>,
+    /// location 中包含的文字，不区分大小写
+    pub location: Option<String>,
+    pub order: SortOrder,
+}
+
+impl BlockQuery {
+    fn condition(&self) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(owner) = self.owner {
+            condition = condition.add(blocks::Column::Pid.eq(owner.to_string()));
+        }
+        if let Some(status) = self.status {
+            condition = condition.add(blocks::Column::Status.eq(status.as_str()));
+        }
+        if let Some(from) = self.from {
+            condition = condition.add(blocks::Column::CreateTime.gte(from));
+        }
+        if let Some(to) = self.to {
+            condition = condition.add(blocks::Column::CreateTime.lt(to));
+        }
+        if let Some(has_images) = self.has_images {
+            let op = if has_images { ">" } else { "=" };
+            condition = condition.add(Expr::cust(format!(
+                "COALESCE(json_array_length(imgs), 0) {op} 0"
+            )));
+        }
+        if let Some(location) = &self.location {
+            condition = condition.add(Expr::cust_with_values(
+                "location ILIKE $1",
+                [format!("%{}%", escape_like(location))],
+            ));
+        }
+        condition
+    }
+}
+
+impl From<&blocks::Model> for Cursor {
+    fn from(block: &blocks::Model) -> Self {
+        Self {
+            create_time: block.create_time,
+            id: block.id,
+        }
+    }
+}
+
+/// 按 (create_time, id) 排序分页：提供 cursor 时取其后的 per_page 条，否则按页码取；
+/// 两种方式都在还有后续数据时返回 next_cursor
+async fn list_blocks(
+    db: &DbConn,
+    condition: Condition,
+    order: SortOrder,
+    cursor: Option<Cursor>,
+    page: u64,
+    per_page: u64,
+) -> Result<Page<blocks::Model>, DbErr> {
+    let query = Block::find().filter(condition);
+    let query = match order {
+        SortOrder::Asc => query
+            .order_by_asc(blocks::Column::CreateTime)
+            .order_by_asc(blocks::Column::Id),
+        SortOrder::Desc => query
+            .order_by_desc(blocks::Column::CreateTime)
+            .order_by_desc(blocks::Column::Id),
+    };
+    let (rows, num_pages, more) = match cursor {
+        Some(cursor) => {
+            let op = match order {
+                SortOrder::Asc => ">",
+                SortOrder::Desc => "<",
+            };
+            let mut rows = query
+                .filter(Expr::cust_with_values(
+                    format!("(create_time, id) {op} ($1, $2)"),
+                    [Value::from(cursor.create_time), Value::from(cursor.id)],
+                ))
+                .limit(per_page + 1)
+                .all(db)
+                .await?;
+            let more = rows.len() as u64 > per_page;
+            rows.truncate(per_page as usize);
+            (rows, None, more)
+        }
+        None => {
+            let paginator = query.paginate(db, per_page);
+            let num_pages = paginator.num_pages().await?;
+            // 页码从 1 开始，0 按第一页处理
+            let page = page.max(1);
+            let rows = paginator.fetch_page(page - 1).await?;
+            (rows, Some(num_pages), page < num_pages)
+        }
+    };
+    let next_cursor = if more {
+        rows.last().map(Cursor::from)
+    } else {
+        None
+    };
+    Ok(Page {
+        rows,
+        num_pages,
+        next_cursor,
+    })
+}
+
+/// 附近查询的结果，distance_m 为与查询点的距离
+#[derive(Serialize, Debug)]
+pub struct NearbyBlock {
+    #[serde(flatten)]
+    pub block: blocks::Model,
+    pub distance_m: f64,
+}
+
+/// 全文检索的结果，rank 越大越相关，highlight 为带 `<mark>` 标记的摘要
+#[derive(Serialize, Debug)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub block: blocks::Model,
+    pub rank: f64,
+    pub highlight: String,
+}
+
+/// 时间线按天或按月汇总
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Day,
+    Month,
+}
+
+impl Granularity {
+    /// 对应的 to_char 格式
+    fn pattern(&self) -> &'static str {
+        match self {
+            Granularity::Day => "YYYY-MM-DD",
+            Granularity::Month => "YYYY-MM",
+        }
+    }
+}
+
+/// 时间线的查询条件
+#[derive(Deserialize, Serialize, Debug, Default, Validate)]
+pub struct TimelineQuery {
+    #[serde(default)]
+    pub granularity: Granularity,
+    /// 创建时间范围，包含 from，不包含 to
+    pub from: Option<DateTimeWithTimeZone>,
+    pub to: Option<DateTimeWithTimeZone>,
+    /// 用户所在时区相对 UTC 的偏移，单位分钟，决定按哪一天汇总，默认 0
+    #[validate(range(min = -720, max = 840, message = "tz_offset must be between -720 and 840 minutes"))]
+    pub tz_offset: Option<i32>,
+}
+
+/// 时间线中的一天或一个月，period 为 `2024-01-01` 或 `2024-01`
+#[derive(Serialize, Debug, PartialEq, FromQueryResult)]
+pub struct TimelineBucket {
+    pub period: String,
+    pub count: i64,
+}
+
+/// 摘要中命中词前后保留的字符数
+const SNIPPET_RADIUS: usize = 40;
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn escape_html(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        '\'' => out.push_str("&#39;"),
+        c => out.push(c),
+    }
+}
+
+/// 生成高亮摘要：截取第一个命中词附近的文本，HTML 转义后用 `<mark>` 包裹所有命中词（不区分大小写）。
+/// 按字符而不是按分词匹配，中文等没有空格分隔的文本同样适用
+pub fn highlight(text: &str, query: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let folded: Vec<char> = chars.iter().map(|c| fold_case(*c)).collect();
+    let mut terms: Vec<Vec<char>> = query
+        .split_whitespace()
+        .map(|t| t.chars().map(fold_case).collect())
+        .collect();
+    // 优先匹配较长的词，避免短词把长词截断
+    terms.sort_by_key(|t| std::cmp::Reverse(t.len()));
+
+    let mut matches: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < folded.len() {
+        match terms.iter().find(|t| folded[i..].starts_with(t)) {
+            Some(term) => {
+                matches.push((i, i + term.len()));
+                i += term.len();
+            }
+            None => i += 1,
+        }
+    }
+
+    let (start, end) = match matches.first() {
+        Some(&(s, e)) => (
+            s.saturating_sub(SNIPPET_RADIUS),
+            (e + SNIPPET_RADIUS).min(chars.len()),
+        ),
+        None => (0, (2 * SNIPPET_RADIUS).min(chars.len())),
+    };
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut pending = matches
+        .iter()
+        .filter(|(s, e)| *e > start && *s < end)
+        .peekable();
+    let mut pos = start;
+    while pos < end {
+        match pending.peek() {
+            Some(&&(s, e)) if s <= pos => {
+                out.push_str("<mark>");
+                for c in &chars[pos..e.min(end)] {
+                    escape_html(&mut out, *c);
+                }
+                out.push_str("</mark>");
+                pos = e.min(end);
+                pending.next();
+            }
+            _ => {
+                escape_html(&mut out, chars[pos]);
+                pos += 1;
+            }
+        }
+    }
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+/// 转义 LIKE 模式中的通配符
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// 两点间的球面距离（haversine 公式），单位米
+pub fn haversine_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// 当前用户可见的 block：自己的全部 block 加上他人已发布的公开 block，回收站中的除外
+fn visible_to(user_id: uuid::Uuid) -> Condition {
+    let published = blocks::Column::Status.eq(BlockStatus::Published.as_str());
+    Condition::all()
+        .add(blocks::Column::DeletedAt.is_null())
+        .add(
+            Condition::any()
+                .add(blocks::Column::Pid.eq(user_id.to_string()))
+                .add(
+                    Condition::all()
+                        .add(published)
+                        .add(blocks::Column::Visibility.eq(Visibility::Public.as_str())),
+                ),
+        )
+}
+
+/// 查询点周围 radius_m 范围的经纬度矩形，跨越 180 度经线时拆成两段
+fn bounding_box(lat: f64, lon: f64, radius_m: f64) -> Condition {
+    let d_lat = radius_m / METERS_PER_DEGREE;
+    let mut condition =
+        Condition::all().add(blocks::Column::Latitude.between(lat - d_lat, lat + d_lat));
+
+    let cos_lat = lat.to_radians().cos();
+    if lat.abs() + d_lat >= 90.0 || cos_lat <= f64::EPSILON {
+        // 靠近极点时经度范围覆盖全部
+        return condition.add(blocks::Column::Longitude.is_not_null());
+    }
+    let d_lon = radius_m / (METERS_PER_DEGREE * cos_lat);
+    if d_lon >= 180.0 {
+        return condition.add(blocks::Column::Longitude.is_not_null());
+    }
+    let (min_lon, max_lon) = (lon - d_lon, lon + d_lon);
+    condition = if min_lon < -180.0 {
+        condition.add(
+            Condition::any()
+                .add(blocks::Column::Longitude.gte(min_lon + 360.0))
+                .add(blocks::Column::Longitude.lte(max_lon)),
+        )
+    } else if max_lon > 180.0 {
+        condition.add(
+            Condition::any()
+                .add(blocks::Column::Longitude.gte(min_lon))
+                .add(blocks::Column::Longitude.lte(max_lon - 360.0)),
+        )
+    } else {
+        condition.add(blocks::Column::Longitude.between(min_lon, max_lon))
+    };
+    condition
 }
 
 pub struct BlockServices;
 
 impl BlockServices {
-    pub async fn create_block(db: &DbConn, form_data: BlockModel, user_id: uuid::Uuid) -> Result<blocks::Model, DbErr> {
+    pub async fn create_block(
+        db: &DbConn,
+        form_data: BlockModel,
+        user_id: uuid::Uuid,
+    ) -> Result<blocks::Model, DbErr> {
         let now = DateTimeWithTimeZone::from(Utc::now());
+        let coordinates = form_data.coordinates();
+        let status = form_data
+            .resolved_status()
+            .unwrap_or(BlockStatus::Published);
         blocks::ActiveModel {
             id: Set(uuid::Uuid::new_v4()),
             pid: Set(Some(user_id.to_string())),
             context: Set(form_data.context),
-            imgs: Set(form_data.imgs.map(|imgs| serde_json::to_value(imgs).unwrap())),
+            imgs: Set(form_data
+                .imgs
+                .map(|imgs| serde_json::to_value(imgs).unwrap())),
             location: Set(form_data.location),
-            latitude_and_longitude: Set(form_data.latitude_and_longitude),
-            draft: Set(form_data.draft),
+            latitude: Set(coordinates.map(|(lat, _)| lat)),
+            longitude: Set(coordinates.map(|(_, lon)| lon)),
+            draft: Set(Some(status.is_draft())),
+            visibility: Set(form_data.visibility.unwrap_or_default().as_str().to_owned()),
+            status: Set(status.as_str().to_owned()),
+            publish_at: Set(form_data.publish_at),
+            published_at: Set((status == BlockStatus::Published).then_some(now)),
             create_time: Set(now),
             update_time: Set(now),
-            ..Default::default()
+            deleted_at: Set(None),
         }
         .insert(db)
         .await
     }
 
-    pub async fn get_block_by_id(db: &DbConn, id: uuid::Uuid) -> Result<Option<blocks::Model>, DbErr> {
-        Block::find_by_id(id).one(db).await
+    pub async fn get_block_by_id(
+        db: &DbConn,
+        id: uuid::Uuid,
+    ) -> Result<Option<blocks::Model>, DbErr> {
+        Block::find_by_id(id)
+            .filter(blocks::Column::DeletedAt.is_null())
+            .one(db)
+            .await
     }
 
-    pub async fn find_blocks(db: &DbConn, page: u64, per_page: u64) -> Result<(Vec<blocks::Model>, u64), DbErr> {
-        let paginator = Block::find()
-            .order_by_asc(blocks::Column::CreateTime)
-            .paginate(db, per_page);
-        let num_pages = paginator.num_pages().await?;
+    pub fn is_owner(block: &blocks::Model, user_id: uuid::Uuid) -> bool {
+        block.pid.as_deref() == Some(user_id.to_string().as_str())
+    }
 
-        paginator.fetch_page(page - 1).await.map(|p| (p, num_pages))
+    /// 作者可以看到自己的全部 block，其他用户只能看到已发布且公开的 block；回收站中的 block 对所有人不可见
+    pub fn is_visible_to(block: &blocks::Model, user_id: uuid::Uuid) -> bool {
+        block.deleted_at.is_none()
+            && (Self::is_owner(block, user_id)
+                || (block.status == BlockStatus::Published.as_str()
+                    && block.visibility == Visibility::Public.as_str()))
     }
 
-    pub async fn find_blocks_by_pid(db: &DbConn, pid: uuid::Uuid, page: u64, per_page: u64) -> Result<(Vec<blocks::Model>, u64), DbErr> {
-        let paginator = Block::find()
-            .filter(blocks::Column::Pid.eq(pid))
-            .order_by_asc(blocks::Column::CreateTime)
-            .paginate(db, per_page);
-        let num_pages = paginator.num_pages().await?;
+    /// 对当前用户不可见的 block 按不存在处理，避免泄露他人草稿
+    pub async fn get_visible_block(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<blocks::Model, ServiceError> {
+        Block::find_by_id(id)
+            .one(db)
+            .await?
+            .filter(|block| Self::is_visible_to(block, user_id))
+            .ok_or(ServiceError::NotFound("Block not found"))
+    }
 
-        paginator.fetch_page(page - 1).await.map(|p| (p, num_pages))
+    /// 只有作者可以修改或删除 block
+    pub async fn get_owned_block(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<blocks::Model, ServiceError> {
+        let block = Self::get_visible_block(db, id, user_id).await?;
+        if !Self::is_owner(&block, user_id) {
+            return Err(ServiceError::Forbidden(
+                "Only the owner can modify this block",
+            ));
+        }
+        Ok(block)
     }
 
-    pub async fn update_block_by_id(db: &DbConn, id: uuid::Uuid, form_data: BlockModel) -> Result<blocks::Model, DbErr> {
-        let block: blocks::ActiveModel = Block::find_by_id(id)
-            .one(db)
+    /// 列出当前用户可见的 block：自己的全部 block 加上他人已发布的公开 block，按 query 过滤
+    pub async fn find_blocks(
+        db: &DbConn,
+        user_id: uuid::Uuid,
+        query: &BlockQuery,
+        cursor: Option<Cursor>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<blocks::Model>, DbErr> {
+        let condition = Condition::all()
+            .add(visible_to(user_id))
+            .add(query.condition());
+        list_blocks(db, condition, query.order, cursor, page, per_page).await
+    }
+
+    /// 查找 (lat, lon) 周围 radius_m 米内当前用户可见的 block，按距离由近到远返回前 limit 条。
+    /// 先用经纬度矩形在数据库中粗筛，再用 haversine 距离精确过滤和排序
+    pub async fn find_blocks_near(
+        db: &DbConn,
+        user_id: uuid::Uuid,
+        lat: f64,
+        lon: f64,
+        radius_m: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyBlock>, DbErr> {
+        let candidates = Block::find()
+            .filter(visible_to(user_id))
+            .filter(bounding_box(lat, lon, radius_m))
+            .all(db)
+            .await?;
+
+        let mut nearby: Vec<NearbyBlock> = candidates
+            .into_iter()
+            .filter_map(|block| {
+                let distance_m = haversine_m(lat, lon, block.latitude?, block.longitude?);
+                (distance_m <= radius_m).then_some(NearbyBlock { block, distance_m })
+            })
+            .collect();
+        nearby.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+        nearby.truncate(limit);
+        Ok(nearby)
+    }
+
+    /// 全文检索当前用户可见的 block。
+    /// 以空格分隔的词走 tsvector 全文索引，中文等连续文本走 pg_trgm 子串匹配，
+    /// 排序综合 ts_rank 与 trigram 相似度
+    pub async fn search_blocks(
+        db: &DbConn,
+        user_id: uuid::Uuid,
+        query: &str,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Vec<SearchHit>, DbErr> {
+        let query = query.trim();
+        let offset = page.saturating_sub(1) * per_page;
+        let stmt = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"WITH q AS (SELECT plainto_tsquery('simple', $1) AS tsq)
+               SELECT b.id, b.pid, b.context, b.imgs, b.location, b.latitude, b.longitude,
+                      b.draft, b.visibility, b.status, b.publish_at, b.published_at,
+                      b.create_time, b.update_time, b.deleted_at,
+                      (ts_rank(b.search_vector, q.tsq)
+                        + word_similarity($1, coalesce(b.context, ''))
+                        + 0.5 * word_similarity($1, coalesce(b.location, '')))::float8 AS rank
+               FROM blocks b, q
+               WHERE (b.search_vector @@ q.tsq OR b.context ILIKE $2 OR b.location ILIKE $2)
+                 AND b.deleted_at IS NULL
+                 AND (b.pid = $3 OR (b.status = $7 AND b.visibility = $4))
+               ORDER BY rank DESC, b.create_time DESC
+               LIMIT $5 OFFSET $6"#,
+            [
+                query.into(),
+                format!("%{}%", escape_like(query)).into(),
+                user_id.to_string().into(),
+                Visibility::Public.as_str().into(),
+                (per_page as i64).into(),
+                (offset as i64).into(),
+                BlockStatus::Published.as_str().into(),
+            ],
+        );
+
+        db.query_all(stmt)
             .await?
-            .ok_or(DbErr::Custom("Cannot find block.".to_owned()))
-            .map(Into::into)?;
+            .iter()
+            .map(|row| {
+                let block = blocks::Model::from_query_result(row, "")?;
+                let rank: f64 = row.try_get("", "rank")?;
+                let highlight = highlight(block.context.as_deref().unwrap_or_default(), query);
+                Ok(SearchHit {
+                    block,
+                    rank,
+                    highlight,
+                })
+            })
+            .collect()
+    }
+
+    /// 某个用户自己的全部 block（包括草稿），回收站中的除外
+    pub async fn find_blocks_by_pid(
+        db: &DbConn,
+        pid: uuid::Uuid,
+        query: &BlockQuery,
+        cursor: Option<Cursor>,
+        page: u64,
+        per_page: u64,
+    ) -> Result<Page<blocks::Model>, DbErr> {
+        let condition = Condition::all()
+            .add(blocks::Column::DeletedAt.is_null())
+            .add(query.condition())
+            .add(blocks::Column::Pid.eq(pid.to_string()));
+        list_blocks(db, condition, query.order, cursor, page, per_page).await
+    }
+
+    /// 是否还有 block 或其历史版本引用 `dir` 目录下的图片，同一 media 的各尺寸位于同一目录；
+    /// 历史版本中的图片也算在内，恢复旧版本后图片仍然可用
+    pub async fn references_media(db: &DbConn, dir: &str) -> Result<bool, DbErr> {
+        let pattern = format!("%/{dir}/%");
+        let blocks = Block::find()
+            .filter(Expr::cust_with_values(
+                "imgs::text LIKE $1",
+                [pattern.clone()],
+            ))
+            .count(db)
+            .await?;
+        if blocks > 0 {
+            return Ok(true);
+        }
+        let revisions = BlockRevision::find()
+            .filter(Expr::cust_with_values("imgs::text LIKE $1", [pattern]))
+            .count(db)
+            .await?;
+        Ok(revisions > 0)
+    }
+
+    /// 用户自己的 block 按天或按月汇总的数量，按时间升序，没有 block 的日期不返回
+    pub async fn timeline(
+        db: &DbConn,
+        pid: uuid::Uuid,
+        query: &TimelineQuery,
+    ) -> Result<Vec<TimelineBucket>, DbErr> {
+        let period = Expr::cust_with_values(
+            "to_char(create_time AT TIME ZONE 'UTC' + make_interval(mins => $1), $2)",
+            [
+                Value::from(query.tz_offset.unwrap_or_default()),
+                Value::from(query.granularity.pattern()),
+            ],
+        );
+        let mut select = Block::find()
+            .select_only()
+            .column_as(period, "period")
+            .column_as(blocks::Column::Id.count(), "count")
+            .filter(blocks::Column::Pid.eq(pid.to_string()))
+            .filter(blocks::Column::DeletedAt.is_null());
+        if let Some(from) = query.from {
+            select = select.filter(blocks::Column::CreateTime.gte(from));
+        }
+        if let Some(to) = query.to {
+            select = select.filter(blocks::Column::CreateTime.lt(to));
+        }
+        select
+            .group_by(Expr::cust("period"))
+            .order_by_asc(Expr::cust("period"))
+            .into_model::<TimelineBucket>()
+            .all(db)
+            .await
+    }
+
+    /// 全部 block 及其历史版本引用的图片 URL，孤儿图片清理据此判断哪些图片仍在使用；
+    /// 回收站中的 block 和历史版本中的图片也保留，恢复后图片仍然可用
+    pub async fn referenced_images(db: &DbConn) -> Result<Vec<String>, DbErr> {
+        let mut imgs: Vec<Option<prelude::Json>> = Block::find()
+            .select_only()
+            .column(blocks::Column::Imgs)
+            .filter(blocks::Column::Imgs.is_not_null())
+            .into_tuple()
+            .all(db)
+            .await?;
+        imgs.extend(
+            BlockRevision::find()
+                .select_only()
+                .column(block_revisions::Column::Imgs)
+                .filter(block_revisions::Column::Imgs.is_not_null())
+                .into_tuple::<Option<prelude::Json>>()
+                .all(db)
+                .await?,
+        );
+        Ok(imgs
+            .into_iter()
+            .flatten()
+            .filter_map(|imgs| serde_json::from_value::<Vec<String>>(imgs).ok())
+            .flatten()
+            .collect())
+    }
+
+    pub async fn update_block_by_id(
+        db: &DbConn,
+        id: uuid::Uuid,
+        form_data: BlockModel,
+    ) -> Result<blocks::Model, ServiceError> {
+        Self::update_with_revision(db, id, None, |block| Self::apply_form(block, form_data)).await
+    }
+
+    pub async fn update_block_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+        form_data: BlockModel,
+    ) -> Result<blocks::Model, ServiceError> {
+        let block = Self::get_owned_block(db, id, user_id).await?;
+        Self::update_block_by_id(db, block.id, form_data).await
+    }
+
+    /// 部分修改 block，expected 为客户端读取时的 update_time（If-Match），省略时不检查
+    pub async fn patch_block_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+        expected: Option<DateTimeWithTimeZone>,
+        patch: BlockPatch,
+    ) -> Result<blocks::Model, ServiceError> {
+        let block = Self::get_owned_block(db, id, user_id).await?;
+        Self::update_with_revision(db, block.id, expected, |block| {
+            Self::apply_patch(block, patch)
+        })
+        .await
+    }
+
+    /// 用历史版本的内容覆盖 block，覆盖前的内容同样保存为新版本，恢复操作本身也可以撤销；发布状态保持不变
+    pub async fn restore_revision_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+        revision: i32,
+    ) -> Result<blocks::Model, ServiceError> {
+        let block = Self::get_owned_block(db, id, user_id).await?;
+        let revision = RevisionServices::get_revision(db, block.id, revision).await?;
+        let block = Self::update_with_revision(db, block.id, None, |block| blocks::ActiveModel {
+            context: Set(revision.context),
+            imgs: Set(revision.imgs),
+            location: Set(revision.location),
+            latitude: Set(revision.latitude),
+            longitude: Set(revision.longitude),
+            visibility: Set(revision.visibility),
+            update_time: Set(DateTimeWithTimeZone::from(Utc::now())),
+            ..block.into()
+        })
+        .await?;
+        Ok(block)
+    }
+
+    fn apply_form(block: blocks::Model, form_data: BlockModel) -> blocks::ActiveModel {
         let now = DateTimeWithTimeZone::from(Utc::now());
-        blocks::ActiveModel {
+        let coordinates = form_data.coordinates();
+        let status = form_data.resolved_status();
+        let block: blocks::ActiveModel = block.into();
+        let mut block = blocks::ActiveModel {
             id: block.id,
             context: Set(form_data.context),
-            imgs: Set(form_data.imgs.map(|imgs| serde_json::to_value(imgs).unwrap())),
+            imgs: Set(form_data
+                .imgs
+                .map(|imgs| serde_json::to_value(imgs).unwrap())),
             location: Set(form_data.location),
-            latitude_and_longitude: Set(form_data.latitude_and_longitude),
-            draft: Set(form_data.draft),
+            latitude: Set(coordinates.map(|(lat, _)| lat)),
+            longitude: Set(coordinates.map(|(_, lon)| lon)),
+            visibility: match form_data.visibility {
+                Some(visibility) => Set(visibility.as_str().to_owned()),
+                None => block.visibility.clone(),
+            },
             update_time: Set(now),
             ..block
+        };
+        if let Some(status) = status {
+            Self::apply_status(&mut block, status, form_data.publish_at, now);
+        }
+        block
+    }
+
+    /// 只修改 PATCH 中提交的字段
+    fn apply_patch(block: blocks::Model, patch: BlockPatch) -> blocks::ActiveModel {
+        let now = DateTimeWithTimeZone::from(Utc::now());
+        let status = patch.resolved_status();
+        let mut block: blocks::ActiveModel = block.into();
+        patch.context.apply(&mut block.context, |context| context);
+        patch
+            .imgs
+            .apply(&mut block.imgs, |imgs| serde_json::to_value(imgs).unwrap());
+        patch
+            .location
+            .apply(&mut block.location, |location| location);
+        patch.latitude.apply(&mut block.latitude, |lat| lat);
+        patch.longitude.apply(&mut block.longitude, |lon| lon);
+        if let Some(visibility) = patch.visibility {
+            block.visibility = Set(visibility.as_str().to_owned());
+        }
+        if let Some(status) = status {
+            Self::apply_status(&mut block, status, patch.publish_at, now);
+        }
+        block.update_time = Set(now);
+        block
+    }
+
+    /// 写入新的状态并同步旧的 draft 字段；第一次发布时记录发布时间，之后修改已发布的 block 不再改变
+    fn apply_status(
+        block: &mut blocks::ActiveModel,
+        status: BlockStatus,
+        publish_at: Option<DateTimeWithTimeZone>,
+        now: DateTimeWithTimeZone,
+    ) {
+        block.draft = Set(Some(status.is_draft()));
+        block.status = Set(status.as_str().to_owned());
+        block.publish_at = Set(publish_at);
+        if status == BlockStatus::Published && block.published_at.as_ref().is_none() {
+            block.published_at = Set(Some(now));
+        }
+    }
+
+    /// 自动保存草稿：只覆盖提交的字段，不保存历史版本，也不改变 published_at
+    /// update_time 是 If-Match 比较的版本，自动保存后同样更新，旧版本的 PATCH 会被拒绝
+    pub async fn autosave_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+        form_data: AutosaveModel,
+    ) -> Result<blocks::Model, ServiceError> {
+        let block = Self::get_owned_block(db, id, user_id).await?;
+        if block.status != BlockStatus::Draft.as_str() {
+            return Err(ServiceError::Invalid("Only drafts can be autosaved"));
+        }
+        let mut block: blocks::ActiveModel = block.into();
+        if let Some(context) = form_data.context {
+            block.context = Set(Some(context));
+        }
+        if let Some(imgs) = form_data.imgs {
+            block.imgs = Set(Some(serde_json::to_value(imgs).unwrap()));
+        }
+        if let Some(location) = form_data.location {
+            block.location = Set(Some(location));
+        }
+        if let (Some(lat), Some(lon)) = (form_data.latitude, form_data.longitude) {
+            block.latitude = Set(Some(lat));
+            block.longitude = Set(Some(lon));
+        }
+        block.update_time = Set(DateTimeWithTimeZone::from(Utc::now()));
+        Ok(block.update(db).await?)
+    }
+
+    /// 发布 publish_at 已到的定时 block，由后台任务定期调用
+    pub async fn publish_due(
+        db: &DbConn,
+        now: DateTimeWithTimeZone,
+    ) -> Result<UpdateResult, DbErr> {
+        Block::update_many()
+            .col_expr(
+                blocks::Column::Status,
+                Expr::value(BlockStatus::Published.as_str()),
+            )
+            .col_expr(blocks::Column::Draft, Expr::value(false))
+            // 撤回后重新定时发布的 block 保留第一次发布的时间
+            .col_expr(
+                blocks::Column::PublishedAt,
+                Func::coalesce([
+                    Expr::col(blocks::Column::PublishedAt).into(),
+                    Expr::col(blocks::Column::PublishAt).into(),
+                ])
+                .into(),
+            )
+            .col_expr(blocks::Column::UpdateTime, Expr::value(now))
+            .filter(blocks::Column::Status.eq(BlockStatus::Scheduled.as_str()))
+            .filter(blocks::Column::PublishAt.lte(now))
+            .filter(blocks::Column::DeletedAt.is_null())
+            .exec(db)
+            .await
+    }
+
+    /// 在事务中锁定 block，先把当前内容保存为历史版本再写入修改；同一 block 的并发修改依次进行
+    /// expected 为客户端读取时的 update_time，与当前不一致时放弃修改
+    async fn update_with_revision(
+        db: &DbConn,
+        id: uuid::Uuid,
+        expected: Option<DateTimeWithTimeZone>,
+        apply: impl FnOnce(blocks::Model) -> blocks::ActiveModel,
+    ) -> Result<blocks::Model, ServiceError> {
+        let txn = db.begin().await?;
+        let block = Block::find_by_id(id)
+            .filter(blocks::Column::DeletedAt.is_null())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(DbErr::RecordNotFound("Cannot find block.".to_owned()))?;
+        ensure_unmodified(block.update_time, expected, "Block has been modified")?;
+        RevisionServices::save_revision(&txn, &block).await?;
+        let block = apply(block).update(&txn).await?;
+        txn.commit().await?;
+        Ok(block)
+    }
+
+    /// 把 block 移入回收站，保留期内可以恢复
+    pub async fn delete_block_by_id(db: &DbConn, id: uuid::Uuid) -> Result<UpdateResult, DbErr> {
+        Block::update_many()
+            .col_expr(
+                blocks::Column::DeletedAt,
+                Expr::value(DateTimeWithTimeZone::from(Utc::now())),
+            )
+            .filter(blocks::Column::Id.eq(id))
+            .filter(blocks::Column::DeletedAt.is_null())
+            .exec(db)
+            .await
+    }
+
+    pub async fn delete_block_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<UpdateResult, ServiceError> {
+        let block = Self::get_owned_block(db, id, user_id).await?;
+        Ok(Self::delete_block_by_id(db, block.id).await?)
+    }
+
+    /// 当前用户回收站中的 block，最近删除的在前
+    pub async fn find_trashed_blocks(
+        db: &DbConn,
+        user_id: uuid::Uuid,
+    ) -> Result<Vec<blocks::Model>, DbErr> {
+        Block::find()
+            .filter(blocks::Column::Pid.eq(user_id.to_string()))
+            .filter(blocks::Column::DeletedAt.is_not_null())
+            .order_by_desc(blocks::Column::DeletedAt)
+            .all(db)
+            .await
+    }
+
+    /// 回收站中属于当前用户的 block，他人的或未删除的按不存在处理
+    pub async fn get_trashed_block(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<blocks::Model, ServiceError> {
+        Block::find_by_id(id)
+            .one(db)
+            .await?
+            .filter(|block| block.deleted_at.is_some() && Self::is_owner(block, user_id))
+            .ok_or(ServiceError::NotFound("Block not found in trash"))
+    }
+
+    pub async fn restore_block_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        user_id: uuid::Uuid,
+    ) -> Result<blocks::Model, ServiceError> {
+        let block = Self::get_trashed_block(db, id, user_id).await?;
+        let block = blocks::ActiveModel {
+            deleted_at: Set(None),
+            update_time: Set(DateTimeWithTimeZone::from(Utc::now())),
+            ..block.into()
         }
         .update(db)
-        .await
+        .await?;
+        Ok(block)
+    }
+
+    /// 在回收站中超过保留期的 block，deleted_at 早于 before
+    pub async fn find_expired_trash(
+        db: &DbConn,
+        before: DateTimeWithTimeZone,
+    ) -> Result<Vec<blocks::Model>, DbErr> {
+        Block::find()
+            .filter(blocks::Column::DeletedAt.lt(before))
+            .all(db)
+            .await
     }
 
-    pub async fn delete_block_by_id(db: &DbConn, id: uuid::Uuid) -> Result<DeleteResult, DbErr> {
+    /// 永久删除 block 及其全部历史版本，图片由调用方处理
+    pub async fn purge_block_by_id(db: &DbConn, id: uuid::Uuid) -> Result<DeleteResult, DbErr> {
+        RevisionServices::delete_revisions(db, id).await?;
         Block::delete_by_id(id).exec(db).await
     }
 }
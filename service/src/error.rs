@@ -0,0 +1,35 @@
+use sea_orm::DbErr;
+use std::fmt;
+
+/// 业务层错误：区分资源不存在、无权访问、参数不合法、超出配额、版本冲突和数据库错误，便于 api 层映射为 404/403/422/413/412/500
+#[derive(Debug)]
+pub enum ServiceError {
+    NotFound(&'static str),
+    Forbidden(&'static str),
+    Invalid(&'static str),
+    QuotaExceeded(&'static str),
+    /// 资源已被修改，与客户端提交的版本不一致
+    PreconditionFailed(&'static str),
+    Db(DbErr),
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::NotFound(msg)
+            | ServiceError::Forbidden(msg)
+            | ServiceError::Invalid(msg)
+            | ServiceError::QuotaExceeded(msg)
+            | ServiceError::PreconditionFailed(msg) => write!(f, "{msg}"),
+            ServiceError::Db(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+impl From<DbErr> for ServiceError {
+    fn from(e: DbErr) -> Self {
+        ServiceError::Db(e)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod error;
 
 pub mod user;
 
@@ -5,10 +6,42 @@ pub mod block;
 
 pub mod search_history;
 
+pub mod refresh_token;
+
+pub mod token_revocation;
+
+pub mod storage;
+
+pub mod media;
+
+pub mod revision;
+
+pub mod patch;
+
+pub mod pagination;
+
+pub mod upload;
+
+pub mod validation;
+
 pub use block::BlockServices;
 
+pub use error::ServiceError;
+
 pub use search_history::SearchHistoryServices;
 
+pub use refresh_token::RefreshTokenServices;
+
+pub use token_revocation::TokenRevocationServices;
+
+pub use storage::StorageServices;
+
+pub use media::MediaServices;
+
+pub use revision::RevisionServices;
+
+pub use upload::UploadServices;
+
 pub use user::UserServices;
 
 pub use sea_orm;
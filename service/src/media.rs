@@ -0,0 +1,113 @@
+use ::entity::{media, media::Entity as Media};
+use chrono::Utc;
+use prelude::DateTimeWithTimeZone;
+use sea_orm::{sea_query::OnConflict, sqlx::types::uuid, *};
+use serde::{Deserialize, Serialize};
+
+use crate::ServiceError;
+
+/// 一张图片的某个尺寸、某种格式在对象存储中的位置
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MediaVariant {
+    pub name: String,
+    pub format: String,
+    pub key: String,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: i64,
+}
+
+/// 新上传图片的元数据
+#[derive(Debug, Clone)]
+pub struct NewMedia {
+    pub id: uuid::Uuid,
+    pub owner: uuid::Uuid,
+    pub object_key: String,
+    /// 原始上传内容的 SHA-256（十六进制）
+    pub content_hash: String,
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<MediaVariant>,
+}
+
+impl NewMedia {
+    /// 全部尺寸实际占用的存储字节数
+    pub fn size(&self) -> i64 {
+        self.variants.iter().map(|v| v.bytes).sum()
+    }
+}
+
+/// 读取 media 记录中保存的各尺寸信息
+pub fn variants_of(media: &media::Model) -> Vec<MediaVariant> {
+    serde_json::from_value(media.variants.clone()).unwrap_or_default()
+}
+
+pub struct MediaServices;
+
+impl MediaServices {
+    /// 写入 media 记录；同一用户并发上传相同内容时 (owner, content_hash) 唯一索引冲突，返回 None
+    pub async fn create_media(db: &DbConn, new: NewMedia) -> Result<Option<media::Model>, DbErr> {
+        let model = media::ActiveModel {
+            id: Set(new.id),
+            owner: Set(new.owner),
+            object_key: Set(new.object_key.clone()),
+            content_hash: Set(new.content_hash.clone()),
+            size: Set(new.size()),
+            mime: Set(new.mime.clone()),
+            width: Set(new.width as i32),
+            height: Set(new.height as i32),
+            variants: Set(serde_json::to_value(&new.variants).unwrap()),
+            create_time: Set(DateTimeWithTimeZone::from(Utc::now())),
+        };
+        let inserted = Media::insert(model.clone())
+            .on_conflict(
+                OnConflict::columns([media::Column::Owner, media::Column::ContentHash])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        model.try_into_model().map(Some)
+    }
+
+    /// 同一用户已上传过的相同内容
+    pub async fn find_by_hash(
+        db: &DbConn,
+        owner: uuid::Uuid,
+        content_hash: &str,
+    ) -> Result<Option<media::Model>, DbErr> {
+        Media::find()
+            .filter(media::Column::Owner.eq(owner))
+            .filter(media::Column::ContentHash.eq(content_hash))
+            .one(db)
+            .await
+    }
+
+    /// 图片只对上传者可见，他人的图片按不存在处理，不暴露 id 是否存在
+    pub async fn get_owned_media(
+        db: &DbConn,
+        id: uuid::Uuid,
+        owner: uuid::Uuid,
+    ) -> Result<media::Model, ServiceError> {
+        Media::find_by_id(id)
+            .one(db)
+            .await?
+            .filter(|media| media.owner == owner)
+            .ok_or(ServiceError::NotFound("Media not found"))
+    }
+
+    /// 删除记录并返回被删除的图片，调用方据此删除对象存储中的文件
+    pub async fn delete_media_for_owner(
+        db: &DbConn,
+        id: uuid::Uuid,
+        owner: uuid::Uuid,
+    ) -> Result<media::Model, ServiceError> {
+        let media = Self::get_owned_media(db, id, owner).await?;
+        Media::delete_by_id(media.id).exec(db).await?;
+        Ok(media)
+    }
+}
@@ -0,0 +1,88 @@
+use std::{fmt, str::FromStr};
+
+use chrono::DateTime;
+use sea_orm::{prelude::DateTimeWithTimeZone, sqlx::types::uuid};
+use serde::{Deserialize, Serialize};
+
+/// 列表接口统一的分页结果：按页码查询时给出 num_pages；还有后续数据时给出 next_cursor，
+/// 客户端可以用它继续加载，不受期间新增或删除数据的影响
+#[derive(Serialize, Debug)]
+pub struct Page<T> {
+    pub rows: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_pages: Option<u64>,
+    pub next_cursor: Option<Cursor>,
+}
+
+impl<T> Page<T> {
+    /// 按页码查询的结果，不提供游标
+    pub fn numbered(rows: Vec<T>, num_pages: u64) -> Self {
+        Self {
+            rows,
+            num_pages: Some(num_pages),
+            next_cursor: None,
+        }
+    }
+}
+
+/// 排序方向，默认按时间升序
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// 按 (create_time, id) 定位的游标，序列化为 `微秒时间戳_id` 字符串，客户端应视为不透明的值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub create_time: DateTimeWithTimeZone,
+    pub id: uuid::Uuid,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.create_time.timestamp_micros(), self.id)
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidCursor;
+
+impl fmt::Display for InvalidCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cursor")
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (micros, id) = s.split_once('_').ok_or(InvalidCursor)?;
+        let create_time = micros
+            .parse()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+            .ok_or(InvalidCursor)?;
+        let id = id.parse().map_err(|_| InvalidCursor)?;
+        Ok(Self {
+            create_time: create_time.into(),
+            id,
+        })
+    }
+}
+
+impl Serialize for Cursor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cursor {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
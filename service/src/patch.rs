@@ -0,0 +1,99 @@
+use std::borrow::Cow;
+
+use sea_orm::{prelude::DateTimeWithTimeZone, ActiveValue};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use validator::{AsRegex, ValidateEmail, ValidateLength, ValidateRange, ValidateRegex};
+
+use crate::ServiceError;
+
+/// PATCH 请求中可为空的字段：区分没有提交、提交 null 和提交了值
+/// 字段需要加 `#[serde(default)]`，没有提交时才会是 Missing
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Patch<T> {
+    #[default]
+    Missing,
+    Null,
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Patch::Missing)
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Patch::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// 把提交的值写入可空列，没有提交时保持原值
+    pub fn apply<V>(self, column: &mut ActiveValue<Option<V>>, f: impl FnOnce(T) -> V)
+    where
+        Option<V>: Into<sea_orm::Value>,
+    {
+        match self {
+            Patch::Missing => {}
+            Patch::Null => *column = ActiveValue::Set(None),
+            Patch::Value(value) => *column = ActiveValue::Set(Some(f(value))),
+        }
+    }
+}
+
+/// 乐观锁检查：expected 为客户端读取时的版本（update_time），省略时不检查
+pub fn ensure_unmodified(
+    current: DateTimeWithTimeZone,
+    expected: Option<DateTimeWithTimeZone>,
+    message: &'static str,
+) -> Result<(), ServiceError> {
+    match expected {
+        Some(expected) if expected != current => Err(ServiceError::PreconditionFailed(message)),
+        _ => Ok(()),
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Patch::Value(value),
+            None => Patch::Null,
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value().serialize(serializer)
+    }
+}
+
+// 以下实现让 Patch 字段可以直接使用 validator 的 length、range、email 和 regex 规则，只校验提交的值
+
+impl<T: ValidateLength<u64>> ValidateLength<u64> for Patch<T> {
+    fn length(&self) -> Option<u64> {
+        self.value().and_then(ValidateLength::length)
+    }
+}
+
+impl<R, T: ValidateRange<R>> ValidateRange<R> for Patch<T> {
+    fn greater_than(&self, max: R) -> Option<bool> {
+        self.value().and_then(|value| value.greater_than(max))
+    }
+
+    fn less_than(&self, min: R) -> Option<bool> {
+        self.value().and_then(|value| value.less_than(min))
+    }
+}
+
+impl<T: ValidateEmail> ValidateEmail for Patch<T> {
+    fn as_email_string(&self) -> Option<Cow<'_, str>> {
+        self.value().and_then(ValidateEmail::as_email_string)
+    }
+}
+
+impl<T: ValidateRegex> ValidateRegex for Patch<T> {
+    fn validate_regex(&self, regex: impl AsRegex) -> bool {
+        self.value().is_none_or(|value| value.validate_regex(regex))
+    }
+}
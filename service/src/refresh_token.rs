@@ -0,0 +1,98 @@
+use ::entity::{refresh_tokens, refresh_tokens::Entity as RefreshToken};
+use chrono::Utc;
+use prelude::DateTimeWithTimeZone;
+use sea_orm::{sea_query::Expr, sqlx::types::uuid, *};
+
+pub struct RefreshTokenServices;
+
+impl RefreshTokenServices {
+    pub async fn create_refresh_token(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<refresh_tokens::Model, DbErr> {
+        refresh_tokens::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            uid: Set(uid),
+            replaced_by: Set(None),
+            expires_at: Set(expires_at),
+            revoked_at: Set(None),
+            create_time: Set(DateTimeWithTimeZone::from(Utc::now())),
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn find_refresh_token_by_id(
+        db: &DbConn,
+        id: uuid::Uuid,
+    ) -> Result<Option<refresh_tokens::Model>, DbErr> {
+        RefreshToken::find_by_id(id).one(db).await
+    }
+
+    /// 轮换 refresh token：吊销旧的并签发新的，两步在同一事务内完成。
+    /// 只有旧 token 尚未被吊销时才会吊销成功，并发使用同一个 token 时只有一个请求能换到新 token，其余返回 None
+    pub async fn rotate_refresh_token(
+        db: &DbConn,
+        old: refresh_tokens::Model,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<Option<refresh_tokens::Model>, DbErr> {
+        let txn = db.begin().await?;
+        let now = DateTimeWithTimeZone::from(Utc::now());
+        let new_token = refresh_tokens::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            uid: Set(old.uid),
+            replaced_by: Set(None),
+            expires_at: Set(expires_at),
+            revoked_at: Set(None),
+            create_time: Set(now),
+        }
+        .insert(&txn)
+        .await?;
+
+        let revoked = RefreshToken::update_many()
+            .col_expr(refresh_tokens::Column::RevokedAt, Expr::value(now))
+            .col_expr(
+                refresh_tokens::Column::ReplacedBy,
+                Expr::value(new_token.id),
+            )
+            .filter(refresh_tokens::Column::Id.eq(old.id))
+            .filter(refresh_tokens::Column::RevokedAt.is_null())
+            .exec(&txn)
+            .await?;
+        if revoked.rows_affected == 0 {
+            txn.rollback().await?;
+            return Ok(None);
+        }
+
+        txn.commit().await?;
+        Ok(Some(new_token))
+    }
+
+    pub async fn revoke_refresh_token(db: &DbConn, id: uuid::Uuid) -> Result<UpdateResult, DbErr> {
+        RefreshToken::update_many()
+            .col_expr(
+                refresh_tokens::Column::RevokedAt,
+                Expr::value(DateTimeWithTimeZone::from(Utc::now())),
+            )
+            .filter(refresh_tokens::Column::Id.eq(id))
+            .filter(refresh_tokens::Column::RevokedAt.is_null())
+            .exec(db)
+            .await
+    }
+
+    pub async fn revoke_all_refresh_tokens_by_uid(
+        db: &DbConn,
+        uid: uuid::Uuid,
+    ) -> Result<UpdateResult, DbErr> {
+        RefreshToken::update_many()
+            .col_expr(
+                refresh_tokens::Column::RevokedAt,
+                Expr::value(DateTimeWithTimeZone::from(Utc::now())),
+            )
+            .filter(refresh_tokens::Column::Uid.eq(uid))
+            .filter(refresh_tokens::Column::RevokedAt.is_null())
+            .exec(db)
+            .await
+    }
+}
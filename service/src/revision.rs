@@ -0,0 +1,215 @@
+use ::entity::{block_revisions, block_revisions::Entity as BlockRevision, blocks};
+use sea_orm::{sqlx::types::uuid, *};
+use serde::{Deserialize, Serialize};
+
+use crate::ServiceError;
+
+/// block 中可以修改、会保存到历史版本的内容
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BlockContent {
+    pub context: Option<String>,
+    pub imgs: Option<Vec<String>>,
+    pub location: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub draft: Option<bool>,
+    pub visibility: String,
+}
+
+fn imgs_of(imgs: &Option<prelude::Json>) -> Option<Vec<String>> {
+    imgs.clone()
+        .and_then(|imgs| serde_json::from_value(imgs).ok())
+}
+
+impl From<&blocks::Model> for BlockContent {
+    fn from(block: &blocks::Model) -> Self {
+        Self {
+            context: block.context.clone(),
+            imgs: imgs_of(&block.imgs),
+            location: block.location.clone(),
+            latitude: block.latitude,
+            longitude: block.longitude,
+            draft: block.draft,
+            visibility: block.visibility.clone(),
+        }
+    }
+}
+
+impl From<&block_revisions::Model> for BlockContent {
+    fn from(revision: &block_revisions::Model) -> Self {
+        Self {
+            context: revision.context.clone(),
+            imgs: imgs_of(&revision.imgs),
+            location: revision.location.clone(),
+            latitude: revision.latitude,
+            longitude: revision.longitude,
+            draft: revision.draft,
+            visibility: revision.visibility.clone(),
+        }
+    }
+}
+
+/// 一个字段修改前后的值
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// context 按行比较的结果
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LineChange {
+    pub op: LineOp,
+    pub text: String,
+}
+
+/// 两个版本之间的差异：changes 列出有变化的字段，context 有变化时另给出逐行差异
+#[derive(Serialize, Debug)]
+pub struct RevisionDiff {
+    pub changes: Vec<FieldChange>,
+    pub context: Vec<LineChange>,
+}
+
+/// 按最长公共子序列逐行比较，context 不超过 5000 字符，平方复杂度可以接受
+pub fn diff_lines(from: &str, to: &str) -> Vec<LineChange> {
+    let a: Vec<&str> = from.lines().collect();
+    let b: Vec<&str> = to.lines().collect();
+    // lcs[i][j] 为 a[i..] 与 b[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut push = |op, text: &str| {
+        changes.push(LineChange {
+            op,
+            text: text.to_string(),
+        })
+    };
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(LineOp::Equal, a[i]);
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(LineOp::Delete, a[i]);
+            i += 1;
+        } else {
+            push(LineOp::Insert, b[j]);
+            j += 1;
+        }
+    }
+    changes
+}
+
+pub fn diff(from: &BlockContent, to: &BlockContent) -> RevisionDiff {
+    let (serde_json::Value::Object(a), serde_json::Value::Object(b)) = (
+        serde_json::to_value(from).unwrap(),
+        serde_json::to_value(to).unwrap(),
+    ) else {
+        unreachable!("BlockContent serializes to an object")
+    };
+    let changes = a
+        .into_iter()
+        .filter(|(field, value)| b.get(field) != Some(value))
+        .map(|(field, value)| FieldChange {
+            to: b[&field].clone(),
+            from: value,
+            field,
+        })
+        .collect();
+    let context = if from.context != to.context {
+        diff_lines(
+            from.context.as_deref().unwrap_or_default(),
+            to.context.as_deref().unwrap_or_default(),
+        )
+    } else {
+        Vec::new()
+    };
+    RevisionDiff { changes, context }
+}
+
+pub struct RevisionServices;
+
+impl RevisionServices {
+    /// 把 block 当前的内容保存为下一个版本，调用方需在锁定 block 的事务中执行，保证版本号连续
+    pub async fn save_revision<C: ConnectionTrait>(
+        db: &C,
+        block: &blocks::Model,
+    ) -> Result<i32, DbErr> {
+        let latest: Option<i32> = BlockRevision::find()
+            .select_only()
+            .column_as(block_revisions::Column::Revision.max(), "revision")
+            .filter(block_revisions::Column::BlockId.eq(block.id))
+            .into_tuple()
+            .one(db)
+            .await?
+            .flatten();
+        let revision = latest.unwrap_or_default() + 1;
+        BlockRevision::insert(block_revisions::ActiveModel {
+            block_id: Set(block.id),
+            revision: Set(revision),
+            context: Set(block.context.clone()),
+            imgs: Set(block.imgs.clone()),
+            location: Set(block.location.clone()),
+            latitude: Set(block.latitude),
+            longitude: Set(block.longitude),
+            draft: Set(block.draft),
+            visibility: Set(block.visibility.clone()),
+            create_time: Set(block.update_time),
+        })
+        .exec_without_returning(db)
+        .await?;
+        Ok(revision)
+    }
+
+    /// block 的全部历史版本，最新的在前
+    pub async fn list_revisions(
+        db: &DbConn,
+        block_id: uuid::Uuid,
+    ) -> Result<Vec<block_revisions::Model>, DbErr> {
+        BlockRevision::find()
+            .filter(block_revisions::Column::BlockId.eq(block_id))
+            .order_by_desc(block_revisions::Column::Revision)
+            .all(db)
+            .await
+    }
+
+    pub async fn get_revision(
+        db: &DbConn,
+        block_id: uuid::Uuid,
+        revision: i32,
+    ) -> Result<block_revisions::Model, ServiceError> {
+        BlockRevision::find_by_id((block_id, revision))
+            .one(db)
+            .await?
+            .ok_or(ServiceError::NotFound("Revision not found"))
+    }
+
+    pub async fn delete_revisions(
+        db: &DbConn,
+        block_id: uuid::Uuid,
+    ) -> Result<DeleteResult, DbErr> {
+        BlockRevision::delete_many()
+            .filter(block_revisions::Column::BlockId.eq(block_id))
+            .exec(db)
+            .await
+    }
+}
@@ -1,71 +1,216 @@
 use ::entity::{search_history, search_history::Entity as SearchHistory};
 use chrono::Utc;
 use prelude::DateTimeWithTimeZone;
-use sea_orm::{sqlx::types::uuid, *};
+use sea_orm::{
+    sea_query::{Expr, OnConflict, SimpleExpr},
+    sqlx::types::uuid,
+    *,
+};
 use serde::{Deserialize, Serialize};
+use validator::Validate;
 
-#[derive(Deserialize, Serialize, Debug)]
+use crate::validation::{validate_search_query, MAX_SEARCH_HISTORY_ITEMS};
+
+/// 只有被至少这么多不同用户搜索过的词才会作为热门联想返回，避免泄露个人搜索记录
+pub const MIN_POPULAR_USERS: i64 = 3;
+
+#[derive(Deserialize, Serialize, Debug, Validate)]
 pub struct SearchHistoryModel {
-    pub history: Option<sea_orm::prelude::Json>,
+    #[validate(custom(function = "validate_search_query"))]
+    pub query: String,
+}
+
+/// 联想词来源
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionSource {
+    History,
+    Popular,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub query: String,
+    pub source: SuggestionSource,
+    pub hits: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct PopularQuery {
+    query: String,
+    hits: i64,
+}
+
+/// 去掉首尾空白并合并连续空白，作为去重的依据
+pub fn normalize_query(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 转义 LIKE 模式中的通配符
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn prefix_condition(prefix: &str) -> SimpleExpr {
+    Expr::cust_with_values(
+        "lower(query) LIKE $1",
+        [format!("{}%", escape_like(&prefix.to_lowercase()))],
+    )
 }
 
 pub struct SearchHistoryServices;
 
 impl SearchHistoryServices {
-    pub async fn create_search_history(db: &DbConn, form_data: SearchHistoryModel, uid: uuid::Uuid) -> Result<search_history::ActiveModel, DbErr> {
+    /// 记录一次搜索：同一用户的相同搜索词只保留一行，累加次数并刷新最近使用时间，
+    /// 超出每个用户的条数上限时淘汰最久未使用的记录
+    pub async fn record_query(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        query: &str,
+    ) -> Result<search_history::Model, DbErr> {
         let now = DateTimeWithTimeZone::from(Utc::now());
-        search_history::ActiveModel {
+        let entry = SearchHistory::insert(search_history::ActiveModel {
             id: Set(uuid::Uuid::new_v4()),
-            uid: Set(Some(uid)),
-            history: Set(form_data.history),
+            uid: Set(uid),
+            query: Set(normalize_query(query)),
+            hits: Set(1),
+            last_used_at: Set(now),
             create_time: Set(now),
-            update_time: Set(now),
-            ..Default::default()
-        }
-        .save(db)
-        .await
+        })
+        .on_conflict(
+            OnConflict::columns([search_history::Column::Uid, search_history::Column::Query])
+                .value(
+                    search_history::Column::Hits,
+                    Expr::col((SearchHistory, search_history::Column::Hits)).add(1),
+                )
+                .update_column(search_history::Column::LastUsedAt)
+                .to_owned(),
+        )
+        .exec_with_returning(db)
+        .await?;
+
+        Self::evict_overflow(db, uid).await?;
+        Ok(entry)
+    }
+
+    /// 删除超出上限的旧记录
+    async fn evict_overflow(db: &DbConn, uid: uuid::Uuid) -> Result<DeleteResult, DbErr> {
+        let keep = SearchHistory::find()
+            .select_only()
+            .column(search_history::Column::Id)
+            .filter(search_history::Column::Uid.eq(uid))
+            .order_by_desc(search_history::Column::LastUsedAt)
+            .limit(MAX_SEARCH_HISTORY_ITEMS as u64)
+            .into_query();
+        SearchHistory::delete_many()
+            .filter(search_history::Column::Uid.eq(uid))
+            .filter(search_history::Column::Id.not_in_subquery(keep))
+            .exec(db)
+            .await
     }
 
-    pub async fn get_search_history_by_id(db: &DbConn, id: uuid::Uuid) -> Result<Option<search_history::Model>, DbErr> {
+    pub async fn get_search_history_by_id(
+        db: &DbConn,
+        id: uuid::Uuid,
+    ) -> Result<Option<search_history::Model>, DbErr> {
         SearchHistory::find_by_id(id).one(db).await
     }
 
-    pub async fn get_search_history_by_uid(db: &DbConn, uid: uuid::Uuid) -> Result<Vec<search_history::Model>, DbErr> {
+    /// 当前用户的搜索历史，最近使用的在前
+    pub async fn get_search_history_by_uid(
+        db: &DbConn,
+        uid: uuid::Uuid,
+    ) -> Result<Vec<search_history::Model>, DbErr> {
         SearchHistory::find()
             .filter(search_history::Column::Uid.eq(uid))
+            .order_by_desc(search_history::Column::LastUsedAt)
             .all(db)
             .await
     }
 
-    pub async fn update_search_history_by_id(db: &DbConn, uid: uuid::Uuid, form_data: SearchHistoryModel) -> Result<search_history::Model, DbErr> {
-        let history: search_history::ActiveModel = SearchHistory::find()
+    /// 删除当前用户的一条搜索记录，不属于该用户的记录按不存在处理
+    pub async fn delete_search_history_entry(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        id: uuid::Uuid,
+    ) -> Result<DeleteResult, DbErr> {
+        let result = SearchHistory::delete_many()
+            .filter(search_history::Column::Id.eq(id))
             .filter(search_history::Column::Uid.eq(uid))
-            .one(db)
-            .await?
-            .ok_or(DbErr::Custom("Cannot find search_history.".to_owned()))
-            .map(Into::into)?;
-        let now = DateTimeWithTimeZone::from(Utc::now());
-        search_history::ActiveModel {
-            id: history.id,
-            uid: history.uid,
-            history: Set(form_data.history),
-            update_time: Set(now),
-            ..history
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(DbErr::RecordNotFound(
+                "Cannot find search_history.".to_owned(),
+            ));
         }
-        .update(db)
-        .await
-    }
-
-    pub async fn delete_search_history_by_id(db: &DbConn, id: uuid::Uuid) -> Result<DeleteResult, DbErr> {
-        SearchHistory::delete_by_id(id).exec(db).await
+        Ok(result)
     }
 
-    pub async fn delete_all_search_history_by_uid(db: &DbConn, uid: uuid::Uuid) -> Result<DeleteResult, DbErr> {
+    pub async fn delete_all_search_history_by_uid(
+        db: &DbConn,
+        uid: uuid::Uuid,
+    ) -> Result<DeleteResult, DbErr> {
         SearchHistory::delete_many()
             .filter(search_history::Column::Uid.eq(uid))
             .exec(db)
             .await
     }
-}
 
+    /// 搜索联想：先返回用户自己以 prefix 开头的历史，再用全站热门搜索词补足 limit 条
+    pub async fn suggest(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        prefix: &str,
+        limit: u64,
+    ) -> Result<Vec<Suggestion>, DbErr> {
+        let prefix = normalize_query(prefix);
 
+        let own = SearchHistory::find()
+            .filter(search_history::Column::Uid.eq(uid))
+            .filter(prefix_condition(&prefix))
+            .order_by_desc(search_history::Column::LastUsedAt)
+            .limit(limit)
+            .all(db)
+            .await?;
+
+        let popular = SearchHistory::find()
+            .select_only()
+            .column(search_history::Column::Query)
+            .column_as(Expr::cust("sum(hits)::int8"), "hits")
+            .filter(prefix_condition(&prefix))
+            .group_by(search_history::Column::Query)
+            .having(Expr::cust_with_values(
+                "count(DISTINCT uid) >= $1",
+                [MIN_POPULAR_USERS],
+            ))
+            .order_by_desc(Expr::cust("sum(hits)"))
+            .limit(limit)
+            .into_model::<PopularQuery>()
+            .all(db)
+            .await?;
+
+        let mut suggestions: Vec<Suggestion> = own
+            .into_iter()
+            .map(|entry| Suggestion {
+                query: entry.query,
+                source: SuggestionSource::History,
+                hits: entry.hits.into(),
+            })
+            .collect();
+        for popular in popular {
+            if !suggestions.iter().any(|s| s.query == popular.query) {
+                suggestions.push(Suggestion {
+                    query: popular.query,
+                    source: SuggestionSource::Popular,
+                    hits: popular.hits,
+                });
+            }
+        }
+        suggestions.truncate(limit as usize);
+        Ok(suggestions)
+    }
+}
@@ -0,0 +1,125 @@
+use ::entity::{storage_usage, storage_usage::Entity as StorageUsage};
+use chrono::Utc;
+use prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    sqlx::types::uuid,
+    *,
+};
+use serde::Serialize;
+
+use crate::ServiceError;
+
+/// 用户存储用量，quota_bytes 为生效的配额（单独设置的配额或默认配额）
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub used_bytes: i64,
+    pub object_count: i32,
+    pub quota_bytes: i64,
+    pub remaining_bytes: i64,
+}
+
+impl Usage {
+    fn new(row: Option<storage_usage::Model>, default_quota: i64) -> Self {
+        let (used_bytes, object_count, quota_bytes) = match row {
+            Some(row) => (
+                row.used_bytes,
+                row.object_count,
+                row.quota_bytes.unwrap_or(default_quota),
+            ),
+            None => (0, 0, default_quota),
+        };
+        Self {
+            used_bytes,
+            object_count,
+            quota_bytes,
+            remaining_bytes: (quota_bytes - used_bytes).max(0),
+        }
+    }
+}
+
+pub struct StorageServices;
+
+impl StorageServices {
+    pub async fn get_usage(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        default_quota: i64,
+    ) -> Result<Usage, DbErr> {
+        let row = StorageUsage::find_by_id(uid).one(db).await?;
+        Ok(Usage::new(row, default_quota))
+    }
+
+    /// 预占存储空间：检查配额和累加用量在同一条 UPDATE 中完成，并发上传也不会超出配额
+    pub async fn reserve(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        bytes: i64,
+        objects: i32,
+        default_quota: i64,
+    ) -> Result<(), ServiceError> {
+        let now = DateTimeWithTimeZone::from(Utc::now());
+        StorageUsage::insert(storage_usage::ActiveModel {
+            uid: Set(uid),
+            used_bytes: Set(0),
+            object_count: Set(0),
+            quota_bytes: Set(None),
+            update_time: Set(now),
+        })
+        .on_conflict(
+            OnConflict::column(storage_usage::Column::Uid)
+                .do_nothing()
+                .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await?;
+
+        let result = StorageUsage::update_many()
+            .col_expr(
+                storage_usage::Column::UsedBytes,
+                Expr::col(storage_usage::Column::UsedBytes).add(bytes),
+            )
+            .col_expr(
+                storage_usage::Column::ObjectCount,
+                Expr::col(storage_usage::Column::ObjectCount).add(objects),
+            )
+            .col_expr(storage_usage::Column::UpdateTime, Expr::value(now))
+            .filter(storage_usage::Column::Uid.eq(uid))
+            .filter(Expr::cust_with_values(
+                "used_bytes + $1 <= COALESCE(quota_bytes, $2)",
+                [bytes, default_quota],
+            ))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(ServiceError::QuotaExceeded("Storage quota exceeded"));
+        }
+        Ok(())
+    }
+
+    /// 释放存储空间，用量不会减到 0 以下
+    pub async fn release(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        bytes: i64,
+        objects: i32,
+    ) -> Result<UpdateResult, DbErr> {
+        StorageUsage::update_many()
+            .col_expr(
+                storage_usage::Column::UsedBytes,
+                Expr::cust_with_values("GREATEST(used_bytes - $1, 0)", [bytes]),
+            )
+            .col_expr(
+                storage_usage::Column::ObjectCount,
+                Expr::cust_with_values("GREATEST(object_count - $1, 0)", [objects]),
+            )
+            .col_expr(
+                storage_usage::Column::UpdateTime,
+                Expr::value(DateTimeWithTimeZone::from(Utc::now())),
+            )
+            .filter(storage_usage::Column::Uid.eq(uid))
+            .exec(db)
+            .await
+    }
+}
@@ -0,0 +1,46 @@
+use ::entity::{token_revocations, token_revocations::Entity as TokenRevocation};
+use chrono::Utc;
+use prelude::DateTimeWithTimeZone;
+use sea_orm::{sqlx::types::uuid, *};
+
+pub struct TokenRevocationServices;
+
+impl TokenRevocationServices {
+    /// 记录一条吊销：jti 为 None 时表示吊销该用户此刻之前签发的全部 token
+    pub async fn create_revocation(
+        db: &DbConn,
+        uid: uuid::Uuid,
+        jti: Option<uuid::Uuid>,
+        expires_at: DateTimeWithTimeZone,
+    ) -> Result<token_revocations::Model, DbErr> {
+        token_revocations::ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            uid: Set(uid),
+            jti: Set(jti),
+            expires_at: Set(expires_at),
+            create_time: Set(DateTimeWithTimeZone::from(Utc::now())),
+        }
+        .insert(db)
+        .await
+    }
+
+    /// 查询仍在有效期内的吊销记录，用于加载内存缓存
+    pub async fn find_active_revocations(
+        db: &DbConn,
+    ) -> Result<Vec<token_revocations::Model>, DbErr> {
+        TokenRevocation::find()
+            .filter(token_revocations::Column::ExpiresAt.gt(DateTimeWithTimeZone::from(Utc::now())))
+            .all(db)
+            .await
+    }
+
+    /// 删除已过期的吊销记录，过期后对应的 token 本身已经失效
+    pub async fn delete_expired_revocations(db: &DbConn) -> Result<DeleteResult, DbErr> {
+        TokenRevocation::delete_many()
+            .filter(
+                token_revocations::Column::ExpiresAt.lte(DateTimeWithTimeZone::from(Utc::now())),
+            )
+            .exec(db)
+            .await
+    }
+}
@@ -0,0 +1,190 @@
+use ::entity::{
+    upload_parts, upload_parts::Entity as UploadPart, upload_sessions,
+    upload_sessions::Entity as UploadSession,
+};
+use chrono::Utc;
+use prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    sqlx::types::uuid,
+    *,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ServiceError;
+
+/// 分片上传会话的状态
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadStatus {
+    Uploading,
+    Completed,
+}
+
+impl UploadStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadStatus::Uploading => "uploading",
+            UploadStatus::Completed => "completed",
+        }
+    }
+}
+
+/// 新建分片上传会话所需的信息，storage_upload_id 为对象存储返回的 upload id
+#[derive(Debug, Clone)]
+pub struct NewUploadSession {
+    pub id: uuid::Uuid,
+    pub owner: uuid::Uuid,
+    pub object_key: String,
+    pub storage_upload_id: String,
+    pub content_type: String,
+    pub size: i64,
+    pub part_size: i64,
+}
+
+/// 会话的分片总数
+pub fn part_count(session: &upload_sessions::Model) -> i32 {
+    ((session.size + session.part_size - 1) / session.part_size) as i32
+}
+
+/// 第 number 个分片应有的大小，编号超出范围时返回 None；只有最后一片可以小于 part_size
+pub fn expected_part_size(session: &upload_sessions::Model, number: i32) -> Option<i64> {
+    let count = part_count(session);
+    if number < 1 || number > count {
+        return None;
+    }
+    if number < count {
+        Some(session.part_size)
+    } else {
+        Some(session.size - session.part_size * (count as i64 - 1))
+    }
+}
+
+pub struct UploadServices;
+
+impl UploadServices {
+    pub async fn create_session(
+        db: &DbConn,
+        new: NewUploadSession,
+    ) -> Result<upload_sessions::Model, DbErr> {
+        let now = DateTimeWithTimeZone::from(Utc::now());
+        upload_sessions::ActiveModel {
+            id: Set(new.id),
+            owner: Set(new.owner),
+            object_key: Set(new.object_key),
+            storage_upload_id: Set(new.storage_upload_id),
+            content_type: Set(new.content_type),
+            size: Set(new.size),
+            part_size: Set(new.part_size),
+            status: Set(UploadStatus::Uploading.as_str().to_owned()),
+            media_id: Set(None),
+            create_time: Set(now),
+            update_time: Set(now),
+        }
+        .insert(db)
+        .await
+    }
+
+    /// 上传会话只对发起者可见，他人的会话按不存在处理
+    pub async fn get_owned_session(
+        db: &DbConn,
+        id: uuid::Uuid,
+        owner: uuid::Uuid,
+    ) -> Result<upload_sessions::Model, ServiceError> {
+        UploadSession::find_by_id(id)
+            .one(db)
+            .await?
+            .filter(|session| session.owner == owner)
+            .ok_or(ServiceError::NotFound("Upload not found"))
+    }
+
+    /// 记录已上传的分片，同一编号重复上传时覆盖，并发上传不同分片互不影响
+    pub async fn put_part(
+        db: &DbConn,
+        upload_id: uuid::Uuid,
+        number: i32,
+        etag: String,
+        size: i64,
+        sha256: String,
+    ) -> Result<(), DbErr> {
+        UploadPart::insert(upload_parts::ActiveModel {
+            upload_id: Set(upload_id),
+            number: Set(number),
+            etag: Set(etag),
+            size: Set(size),
+            sha256: Set(sha256),
+            create_time: Set(DateTimeWithTimeZone::from(Utc::now())),
+        })
+        .on_conflict(
+            OnConflict::columns([upload_parts::Column::UploadId, upload_parts::Column::Number])
+                .update_columns([
+                    upload_parts::Column::Etag,
+                    upload_parts::Column::Size,
+                    upload_parts::Column::Sha256,
+                    upload_parts::Column::CreateTime,
+                ])
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(())
+    }
+
+    /// 已上传的分片，按编号排序
+    pub async fn list_parts(
+        db: &DbConn,
+        upload_id: uuid::Uuid,
+    ) -> Result<Vec<upload_parts::Model>, DbErr> {
+        UploadPart::find()
+            .filter(upload_parts::Column::UploadId.eq(upload_id))
+            .order_by_asc(upload_parts::Column::Number)
+            .all(db)
+            .await
+    }
+
+    /// 标记会话已完成并删除分片记录，之后重复 complete 直接返回 media_id 对应的图片
+    pub async fn mark_completed(
+        db: &DbConn,
+        id: uuid::Uuid,
+        media_id: uuid::Uuid,
+    ) -> Result<(), DbErr> {
+        UploadSession::update_many()
+            .col_expr(
+                upload_sessions::Column::Status,
+                Expr::value(UploadStatus::Completed.as_str()),
+            )
+            .col_expr(upload_sessions::Column::MediaId, Expr::value(media_id))
+            .col_expr(
+                upload_sessions::Column::UpdateTime,
+                Expr::value(DateTimeWithTimeZone::from(Utc::now())),
+            )
+            .filter(upload_sessions::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        UploadPart::delete_many()
+            .filter(upload_parts::Column::UploadId.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_session(db: &DbConn, id: uuid::Uuid) -> Result<(), DbErr> {
+        UploadPart::delete_many()
+            .filter(upload_parts::Column::UploadId.eq(id))
+            .exec(db)
+            .await?;
+        UploadSession::delete_by_id(id).exec(db).await?;
+        Ok(())
+    }
+
+    /// 创建时间早于 before 的会话，由清理任务放弃或删除
+    pub async fn find_expired(
+        db: &DbConn,
+        before: DateTimeWithTimeZone,
+    ) -> Result<Vec<upload_sessions::Model>, DbErr> {
+        UploadSession::find()
+            .filter(upload_sessions::Column::CreateTime.lt(before))
+            .all(db)
+            .await
+    }
+}
@@ -1,21 +1,84 @@
 use ::entity::{users, users::Entity as User};
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use prelude::DateTimeWithTimeZone;
 use sea_orm::{sqlx::types::uuid, *};
 use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
 
-#[derive(Deserialize, Serialize, Debug)]
+use crate::{
+    error::ServiceError,
+    pagination::Page,
+    patch::{ensure_unmodified, Patch},
+    validation::{validate_sex, PHONE_RE},
+};
+
+#[derive(Deserialize, Serialize, Debug, Validate)]
 pub struct UserModel {
     pub id: uuid::Uuid,
+    #[validate(length(min = 1, max = 50, message = "name must be 1 to 50 characters"))]
     pub name: String,
+    #[validate(custom(function = "validate_sex"))]
     pub sex: String,
+    #[validate(email(message = "invalid email address"))]
     pub email: String,
+    #[validate(regex(path = *PHONE_RE, message = "invalid phone number"))]
     pub phone: String,
     pub birthday: Option<DateTimeWithTimeZone>,
 }
 
+/// PATCH 修改用户时提交的内容：只修改提交的字段，提交 null 表示清空
+#[derive(Deserialize, Serialize, Debug, Default, Validate)]
+#[serde(default)]
+pub struct UserPatch {
+    #[validate(length(min = 1, max = 50, message = "name must be 1 to 50 characters"))]
+    pub name: Patch<String>,
+    #[validate(custom(function = "validate_patch_sex"))]
+    pub sex: Patch<String>,
+    #[validate(email(message = "invalid email address"))]
+    pub email: Patch<String>,
+    #[validate(regex(path = *PHONE_RE, message = "invalid phone number"))]
+    pub phone: Patch<String>,
+    pub birthday: Patch<DateTimeWithTimeZone>,
+}
+
+fn validate_patch_sex(sex: &Patch<String>) -> Result<(), ValidationError> {
+    sex.value().map_or(Ok(()), |sex| validate_sex(sex))
+}
+
+/// 用户角色，对应 users.role 列
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// 解析数据库中的角色，未知值按普通用户处理
+    pub fn from_db(role: &str) -> Self {
+        match role {
+            "admin" => Role::Admin,
+            _ => Role::User,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
+pub struct RoleModel {
+    pub role: Role,
+}
+
+#[derive(Deserialize, Serialize, Debug, Validate)]
 pub struct LoginModel {
+    #[validate(length(min = 1, max = 128, message = "js_code must not be empty"))]
     pub js_code: String,
 }
 
@@ -25,11 +88,11 @@ impl UserServices {
     pub async fn create_user(
         db: &DbConn,
         form_data: UserModel,
-    ) -> Result<users::Model, DbErr> {
+    ) -> Result<users::Model, ServiceError> {
         let sex = form_data
             .sex
             .parse::<i32>()
-            .map_err(|_| DbErr::Custom("性别必须是数字".to_string()))?;
+            .map_err(|_| ServiceError::Invalid("性别必须是数字"))?;
         let userid = uuid::Uuid::new_v4();
         users::ActiveModel {
             id: Set(userid),
@@ -38,6 +101,7 @@ impl UserServices {
             email: Set(Some(form_data.email)),
             phone: Set(Some(form_data.phone.to_owned())),
             birthday: Set(form_data.birthday),
+            role: Set(Role::User.as_str().to_owned()),
             created_at: Set(DateTimeWithTimeZone::from(Utc::now())),
             updated_at: Set(DateTimeWithTimeZone::from(Utc::now())),
             ..Default::default()
@@ -50,38 +114,94 @@ impl UserServices {
             .one(db)
             .await?
             .ok_or(DbErr::Custom("Created user not found".to_string()))
+            .map_err(Into::into)
     }
 
     pub async fn update_user_by_id(
         db: &DbConn,
         id: uuid::Uuid,
         form_data: UserModel,
-    ) -> Result<users::Model, DbErr> {
+    ) -> Result<users::Model, ServiceError> {
         let users: users::ActiveModel = User::find_by_id(id)
             .one(db)
             .await?
-            .ok_or(DbErr::Custom("Cannot find users.".to_owned()))
+            .ok_or(DbErr::RecordNotFound("Cannot find users.".to_owned()))
             .map(Into::into)?;
-        let sex: i32 = form_data.sex.parse().expect("msg");
+        let sex: i32 = form_data
+            .sex
+            .parse()
+            .map_err(|_| ServiceError::Invalid("性别必须是数字"))?;
         users::ActiveModel {
-            id: users.id,
             name: Set(Some(form_data.name.to_owned())),
             email: Set(Some(form_data.email)),
             sex: Set(Some(sex)),
             phone: Set(Some(form_data.phone)),
             birthday: Set(form_data.birthday),
             updated_at: Set(DateTimeWithTimeZone::from(Utc::now())),
-            ..Default::default()
+            ..users
         }
         .update(db)
         .await
+        .map_err(Into::into)
+    }
+
+    /// 只修改提交的字段；expected 为客户端读取时的 updated_at（If-Match），省略时不检查
+    pub async fn patch_user_by_id(
+        db: &DbConn,
+        id: uuid::Uuid,
+        expected: Option<DateTimeWithTimeZone>,
+        patch: UserPatch,
+    ) -> Result<users::Model, ServiceError> {
+        let sex = match patch.sex {
+            Patch::Value(sex) => Patch::Value(
+                sex.parse::<i32>()
+                    .map_err(|_| ServiceError::Invalid("性别必须是数字"))?,
+            ),
+            Patch::Null => Patch::Null,
+            Patch::Missing => Patch::Missing,
+        };
+        // 锁定用户行，保证版本检查和写入之间不会被其他请求修改
+        let txn = db.begin().await?;
+        let user = User::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(DbErr::RecordNotFound("Cannot find users.".to_owned()))?;
+        ensure_unmodified(user.updated_at, expected, "User has been modified")?;
+        let mut user: users::ActiveModel = user.into();
+        patch.name.apply(&mut user.name, |name| name);
+        sex.apply(&mut user.sex, |sex| sex);
+        patch.email.apply(&mut user.email, |email| email);
+        patch.phone.apply(&mut user.phone, |phone| phone);
+        patch
+            .birthday
+            .apply(&mut user.birthday, |birthday| birthday);
+        user.updated_at = Set(DateTimeWithTimeZone::from(Utc::now()));
+        let user = user.update(&txn).await?;
+        txn.commit().await?;
+        Ok(user)
+    }
+
+    pub async fn update_user_role(
+        db: &DbConn,
+        id: uuid::Uuid,
+        role: Role,
+    ) -> Result<users::Model, DbErr> {
+        let mut user: users::ActiveModel = User::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::RecordNotFound("Cannot find users.".to_owned()))
+            .map(Into::into)?;
+        user.role = Set(role.as_str().to_owned());
+        user.updated_at = Set(DateTimeWithTimeZone::from(Utc::now()));
+        user.update(db).await
     }
 
     pub async fn delete_user(db: &DbConn, id: uuid::Uuid) -> Result<DeleteResult, DbErr> {
         let users: users::ActiveModel = User::find_by_id(id)
             .one(db)
             .await?
-            .ok_or(DbErr::Custom("Cannot find users.".to_owned()))
+            .ok_or(DbErr::RecordNotFound("Cannot find users.".to_owned()))
             .map(Into::into)?;
 
         users.delete(db).await
@@ -95,13 +215,15 @@ impl UserServices {
         db: &DbConn,
         page: u64,
         per_page: u64,
-    ) -> Result<(Vec<users::Model>, u64), DbErr> {
+    ) -> Result<Page<users::Model>, DbErr> {
         let paginator = User::find()
             .order_by_asc(users::Column::Id)
             .paginate(db, per_page);
         let num_pages = paginator.num_pages().await?;
 
-        paginator.fetch_page(page - 1).await.map(|p| (p, num_pages))
+        // 页码从 1 开始，0 按第一页处理
+        let users = paginator.fetch_page(page.max(1) - 1).await?;
+        Ok(Page::numbered(users, num_pages))
     }
 
     pub async fn find_user_by_id(
@@ -129,6 +251,7 @@ impl UserServices {
             id: Set(uuid::Uuid::new_v4()),
             sex: Set(Some(0)),
             app_id: Set(appid.to_string()),
+            role: Set(Role::User.as_str().to_owned()),
             created_at: Set(now),
             updated_at: Set(now),
             ..Default::default()
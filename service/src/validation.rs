@@ -0,0 +1,67 @@
+use std::{borrow::Cow, sync::LazyLock};
+
+use regex::Regex;
+use validator::ValidationError;
+
+/// 手机号：可带国际区号前缀 `+`，6 到 15 位数字
+pub static PHONE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\+?[0-9]{6,15}$").unwrap());
+
+/// 单条搜索记录的最大长度
+pub const MAX_SEARCH_QUERY_LEN: usize = 100;
+
+/// 每个用户最多保存的搜索历史条数，超出时淘汰最久未使用的记录
+pub const MAX_SEARCH_HISTORY_ITEMS: usize = 50;
+
+fn error(code: &'static str, message: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(Cow::Borrowed(message))
+}
+
+/// 性别：0 未知，1 男，2 女（与微信用户信息一致）
+pub fn validate_sex(sex: &str) -> Result<(), ValidationError> {
+    match sex {
+        "0" | "1" | "2" => Ok(()),
+        _ => Err(error(
+            "sex",
+            "sex must be 0 (unknown), 1 (male) or 2 (female)",
+        )),
+    }
+}
+
+/// 解析 `纬度,经度` 格式的坐标，超出范围时返回 None
+pub fn parse_lat_lng(value: &str) -> Option<(f64, f64)> {
+    let (lat, lng) = value.split_once(',')?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lng: f64 = lng.trim().parse().ok()?;
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng)).then_some((lat, lng))
+}
+
+pub fn validate_lat_lng(value: &str) -> Result<(), ValidationError> {
+    parse_lat_lng(value).map(|_| ()).ok_or_else(|| {
+        error(
+            "lat_lng",
+            "must be `latitude,longitude` within valid ranges",
+        )
+    })
+}
+
+/// 图片地址必须是 http(s) URL
+pub fn validate_image_urls(imgs: &[String]) -> Result<(), ValidationError> {
+    let valid = imgs.iter().all(|url| {
+        (url.starts_with("https://") || url.starts_with("http://"))
+            && !url.chars().any(char::is_whitespace)
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(error("url", "every image must be an http(s) URL"))
+    }
+}
+
+/// 搜索词去掉首尾空白后为 1 到 100 个字符
+pub fn validate_search_query(query: &str) -> Result<(), ValidationError> {
+    let len = query.trim().chars().count();
+    if len == 0 || len > MAX_SEARCH_QUERY_LEN {
+        return Err(error("query", "query must be 1 to 100 characters"));
+    }
+    Ok(())
+}
@@ -1,79 +1,872 @@
 mod prepare;
 
-use axum_example_service::{Mutation, Query};
-use entity::post;
-use prepare::prepare_mock_db;
+use prepare::{block_id, prepare_mock_db};
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Utc};
+use sea_orm::{
+    prelude::DateTimeWithTimeZone, DatabaseBackend, IntoMockRow, MockDatabase, MockExecResult,
+    Value,
+};
+use service::{
+    block::{
+        haversine_m, highlight, AutosaveModel, BlockModel, BlockPatch, BlockQuery, BlockStatus,
+        Granularity, TimelineBucket, TimelineQuery,
+    },
+    media::NewMedia,
+    pagination::{Cursor, SortOrder},
+    patch::Patch,
+    revision::{diff, diff_lines, BlockContent, LineOp},
+    search_history::SuggestionSource,
+    user::UserPatch,
+    BlockServices, MediaServices, SearchHistoryServices, ServiceError, StorageServices,
+    UserServices,
+};
+use validator::Validate;
 
 #[tokio::test]
 async fn main() {
     let db = &prepare_mock_db();
 
     {
-        let post = Query::find_post_by_id(db, 1).await.unwrap().unwrap();
+        let block = BlockServices::get_block_by_id(db, block_id(1))
+            .await
+            .unwrap()
+            .unwrap();
 
-        assert_eq!(post.id, 1);
+        assert_eq!(block.id, block_id(1));
     }
 
     {
-        let post = Query::find_post_by_id(db, 5).await.unwrap().unwrap();
+        let block = BlockServices::get_block_by_id(db, block_id(5))
+            .await
+            .unwrap()
+            .unwrap();
 
-        assert_eq!(post.id, 5);
+        assert_eq!(block.id, block_id(5));
     }
 
     {
-        let post = Mutation::create_post(
+        let block = BlockServices::create_block(
             db,
-            post::Model {
-                id: 0,
-                title: "Title D".to_owned(),
-                text: "Text D".to_owned(),
+            BlockModel {
+                context: Some("Context D".to_owned()),
+                imgs: None,
+                location: None,
+                draft: Some(false),
+                ..Default::default()
             },
+            block_id(100),
         )
         .await
         .unwrap();
 
-        assert_eq!(
-            post,
-            post::ActiveModel {
-                id: sea_orm::ActiveValue::Unchanged(6),
-                title: sea_orm::ActiveValue::Unchanged("Title D".to_owned()),
-                text: sea_orm::ActiveValue::Unchanged("Text D".to_owned())
-            }
-        );
+        assert_eq!(block.context.as_deref(), Some("Context D"));
     }
 
     {
-        let post = Mutation::update_post_by_id(
+        let block = BlockServices::update_block_by_id(
             db,
-            1,
-            post::Model {
-                id: 1,
-                title: "New Title A".to_owned(),
-                text: "New Text A".to_owned(),
+            block_id(1),
+            BlockModel {
+                context: Some("New Context A".to_owned()),
+                imgs: None,
+                location: None,
+                draft: Some(false),
+                ..Default::default()
             },
         )
         .await
         .unwrap();
 
-        assert_eq!(
-            post,
-            post::Model {
-                id: 1,
-                title: "New Title A".to_owned(),
-                text: "New Text A".to_owned(),
-            }
-        );
+        assert_eq!(block.context.as_deref(), Some("New Context A"));
     }
 
     {
-        let result = Mutation::delete_post(db, 5).await.unwrap();
+        let result = BlockServices::delete_block_by_id(db, block_id(5))
+            .await
+            .unwrap();
 
         assert_eq!(result.rows_affected, 1);
     }
+}
 
-    {
-        let result = Mutation::delete_all_posts(db).await.unwrap();
+#[tokio::test]
+async fn block_ownership() {
+    let owner = block_id(100);
+    let stranger = block_id(200);
+    let mut public = prepare::block(2, "Public");
+    public.visibility = "public".to_owned();
+    let mut draft = prepare::block(3, "Draft");
+    draft.visibility = "public".to_owned();
+    draft.draft = Some(true);
+    draft.status = "draft".to_owned();
+
+    let db = &MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[public.clone()], [draft.clone()], [draft], [public]])
+        .into_connection();
+
+    assert!(BlockServices::get_visible_block(db, block_id(2), stranger)
+        .await
+        .is_ok());
+    assert!(matches!(
+        BlockServices::get_visible_block(db, block_id(3), stranger).await,
+        Err(ServiceError::NotFound(_))
+    ));
+    assert!(BlockServices::get_owned_block(db, block_id(3), owner)
+        .await
+        .is_ok());
+    assert!(matches!(
+        BlockServices::get_owned_block(db, block_id(2), stranger).await,
+        Err(ServiceError::Forbidden(_))
+    ));
+}
+
+#[tokio::test]
+async fn blocks_near_are_filtered_and_sorted_by_distance() {
+    let located = |n, lat, lon| {
+        let mut block = prepare::block(n, "Located");
+        block.latitude = Some(lat);
+        block.longitude = Some(lon);
+        block
+    };
+    // 数据库按矩形粗筛返回的候选，其中 (31.2400, 121.5000) 在矩形角上但超出半径
+    let db = &MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![
+            located(1, 31.2300, 121.4800),
+            located(2, 31.2400, 121.5000),
+            located(3, 31.2305, 121.4737),
+        ]])
+        .into_connection();
+
+    let nearby = BlockServices::find_blocks_near(db, block_id(100), 31.2304, 121.4737, 1000.0, 10)
+        .await
+        .unwrap();
+
+    let ids: Vec<_> = nearby.iter().map(|n| n.block.id).collect();
+    assert_eq!(ids, vec![block_id(3), block_id(1)]);
+    assert!(nearby[0].distance_m < nearby[1].distance_m);
+}
+
+#[test]
+fn haversine_distance() {
+    // 上海到北京约 1068 公里
+    let d = haversine_m(31.2304, 121.4737, 39.9042, 116.4074);
+    assert!((d - 1_068_000.0).abs() < 5_000.0, "{d}");
+}
+
+#[tokio::test]
+async fn search_returns_ranked_hits_with_highlight() {
+    let row = |n, context: &str, rank: f64| {
+        let mut values: BTreeMap<String, Value> = prepare::block(n, context)
+            .into_mock_row()
+            .into_column_value_tuples()
+            .collect();
+        values.insert("rank".to_owned(), rank.into());
+        values
+    };
+    let db = &MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![
+            row(1, "今天在外滩喝了一杯咖啡", 0.9),
+            row(2, "Coffee & cake", 0.4),
+        ]])
+        .into_connection();
+
+    let hits = BlockServices::search_blocks(db, block_id(100), " 咖啡 ", 1, 10)
+        .await
+        .unwrap();
+
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].block.id, block_id(1));
+    assert_eq!(hits[0].rank, 0.9);
+    assert_eq!(hits[0].highlight, "今天在外滩喝了一杯<mark>咖啡</mark>");
+}
+
+#[test]
+fn highlight_escapes_html_and_trims_long_text() {
+    assert_eq!(
+        highlight("<b>Coffee</b> and coffee", "COFFEE"),
+        "&lt;b&gt;<mark>Coffee</mark>&lt;/b&gt; and <mark>coffee</mark>"
+    );
+
+    let long = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+    let snippet = highlight(&long, "needle");
+    assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+    assert!(snippet.contains("<mark>needle</mark>"));
+}
+
+#[tokio::test]
+async fn search_query_is_upserted_and_overflow_evicted() {
+    let now = chrono::Utc::now().into();
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[entity::search_history::Model {
+            id: block_id(10),
+            uid: block_id(100),
+            query: "coffee beans".to_owned(),
+            hits: 2,
+            last_used_at: now,
+            create_time: now,
+        }]])
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 0,
+        }])
+        .into_connection();
+
+    let saved = SearchHistoryServices::record_query(&db, block_id(100), "  coffee   beans ")
+        .await
+        .unwrap();
+
+    assert_eq!(saved.hits, 2);
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("ON CONFLICT"));
+    assert!(log.contains("coffee beans"));
+    assert!(log.contains(r#"DELETE FROM \"search_history\""#));
+}
+
+#[tokio::test]
+async fn suggestions_put_own_history_before_popular_queries() {
+    let now = chrono::Utc::now().into();
+    let popular = |query: &str, hits: i64| {
+        BTreeMap::from([
+            ("query", Value::from(query.to_owned())),
+            ("hits", Value::from(hits)),
+        ])
+        .into_mock_row()
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[entity::search_history::Model {
+            id: block_id(10),
+            uid: block_id(100),
+            query: "coffee".to_owned(),
+            hits: 1,
+            last_used_at: now,
+            create_time: now,
+        }]])
+        .append_query_results([[
+            popular("coffee", 40),
+            popular("coffee shop", 12),
+            popular("coffee beans", 7),
+        ]])
+        .into_connection();
+
+    let suggestions = SearchHistoryServices::suggest(&db, block_id(100), "Cof", 2)
+        .await
+        .unwrap();
+
+    let queries: Vec<_> = suggestions
+        .iter()
+        .map(|s| (s.query.as_str(), s.source))
+        .collect();
+    assert_eq!(
+        queries,
+        [
+            ("coffee", SuggestionSource::History),
+            ("coffee shop", SuggestionSource::Popular)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn deleting_another_users_history_entry_is_not_found() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 0,
+        }])
+        .into_connection();
+
+    let result =
+        SearchHistoryServices::delete_search_history_entry(&db, block_id(100), block_id(10)).await;
+
+    assert!(matches!(result, Err(sea_orm::DbErr::RecordNotFound(_))));
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"\"uid\" = $2"#));
+}
+
+#[tokio::test]
+async fn reserving_beyond_quota_is_rejected() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 0,
+            },
+        ])
+        .into_connection();
+
+    let result = StorageServices::reserve(&db, block_id(100), 4096, 2, 1024).await;
+
+    assert!(matches!(result, Err(ServiceError::QuotaExceeded(_))));
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("ON CONFLICT"));
+    assert!(log.contains("used_bytes + $5 <= COALESCE(quota_bytes, $6)"));
+}
+
+#[tokio::test]
+async fn media_of_other_users_is_not_found() {
+    let now = chrono::Utc::now().into();
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[entity::media::Model {
+            id: block_id(20),
+            owner: block_id(101),
+            object_key: "images/101/20/original.webp".to_owned(),
+            content_hash: "ab".repeat(32),
+            size: 2048,
+            mime: "image/jpeg".to_owned(),
+            width: 640,
+            height: 480,
+            variants: serde_json::json!([]),
+            create_time: now,
+        }]])
+        .into_connection();
+
+    let result = MediaServices::delete_media_for_owner(&db, block_id(20), block_id(100)).await;
+
+    assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    // 没有执行 DELETE
+    assert_eq!(db.into_transaction_log().len(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_duplicate_media_insert_returns_none() {
+    // ON CONFLICT DO NOTHING 没有返回行，说明另一个请求已经写入了相同内容
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 0,
+        }])
+        .into_connection();
+
+    let created = MediaServices::create_media(
+        &db,
+        NewMedia {
+            id: block_id(20),
+            owner: block_id(100),
+            object_key: "images/100/20/original.webp".to_owned(),
+            content_hash: "ab".repeat(32),
+            mime: "image/jpeg".to_owned(),
+            width: 640,
+            height: 480,
+            variants: vec![],
+        },
+    )
+    .await
+    .unwrap();
 
-        assert_eq!(result.rows_affected, 5);
+    assert!(created.is_none());
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"ON CONFLICT (\"owner\", \"content_hash\") DO NOTHING"#));
+}
+
+#[tokio::test]
+async fn media_referenced_by_a_block_is_detected() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            [BTreeMap::from([("num_items", Value::from(1i64))]).into_mock_row()],
+        ])
+        .into_connection();
+
+    let referenced = BlockServices::references_media(&db, "images/100/20")
+        .await
+        .unwrap();
+
+    assert!(referenced);
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("imgs::text LIKE $1"));
+    assert!(log.contains("%/images/100/20/%"));
+}
+
+#[tokio::test]
+async fn media_referenced_only_by_a_revision_is_detected() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            [BTreeMap::from([("num_items", Value::from(0i64))]).into_mock_row()],
+        ])
+        .append_query_results([
+            [BTreeMap::from([("num_items", Value::from(1i64))]).into_mock_row()],
+        ])
+        .into_connection();
+
+    let referenced = BlockServices::references_media(&db, "images/100/20")
+        .await
+        .unwrap();
+
+    assert!(referenced);
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"FROM \"block_revisions\""#));
+}
+
+#[tokio::test]
+async fn updating_a_block_saves_the_previous_revision() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[prepare::block(1, "Context A")]])
+        .append_query_results([[BTreeMap::from([("revision", Value::Int(Some(2)))])]])
+        .append_query_results([[prepare::block(1, "New Context A")]])
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 1,
+        }])
+        .into_connection();
+
+    BlockServices::update_block_by_id(
+        &db,
+        block_id(1),
+        BlockModel {
+            context: Some("New Context A".to_owned()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("FOR UPDATE"));
+    assert!(log.contains(r#"INSERT INTO \"block_revisions\""#));
+    // 新版本号为已有最大版本号加一，内容为修改前的 context
+    assert!(log.contains("Int(Some(3))"));
+    assert!(log.contains(r#"String(Some("Context A"))"#));
+}
+
+#[test]
+fn revisions_are_diffed_by_field_and_line() {
+    let mut old = prepare::block(1, "first\nsecond\nthird");
+    old.location = Some("Shanghai".to_owned());
+    let mut new = old.clone();
+    new.context = Some("first\n2nd\nthird\nfourth".to_owned());
+    new.visibility = "public".to_owned();
+
+    let diff = diff(&BlockContent::from(&old), &BlockContent::from(&new));
+
+    let fields: Vec<&str> = diff.changes.iter().map(|c| c.field.as_str()).collect();
+    assert_eq!(fields, ["context", "visibility"]);
+    assert_eq!(diff.changes[1].from, "private");
+    assert_eq!(diff.changes[1].to, "public");
+    let ops: Vec<(LineOp, &str)> = diff
+        .context
+        .iter()
+        .map(|c| (c.op, c.text.as_str()))
+        .collect();
+    assert_eq!(
+        ops,
+        [
+            (LineOp::Equal, "first"),
+            (LineOp::Delete, "second"),
+            (LineOp::Insert, "2nd"),
+            (LineOp::Equal, "third"),
+            (LineOp::Insert, "fourth"),
+        ]
+    );
+    assert!(diff_lines("same", "same")
+        .iter()
+        .all(|c| c.op == LineOp::Equal));
+}
+
+#[tokio::test]
+async fn deleted_blocks_move_to_the_trash() {
+    let owner = block_id(100);
+    let mut trashed = prepare::block(1, "Trashed");
+    trashed.deleted_at = Some(trashed.update_time);
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[prepare::block(1, "Context A")], [trashed.clone()]])
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 1,
+        }])
+        .into_connection();
+
+    let result = BlockServices::delete_block_for_owner(&db, block_id(1), owner)
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 1);
+    // 回收站中的 block 对作者也不可见，只能通过回收站接口恢复
+    assert!(!BlockServices::is_visible_to(&trashed, owner));
+    assert!(matches!(
+        BlockServices::get_trashed_block(&db, block_id(1), block_id(200)).await,
+        Err(ServiceError::NotFound(_))
+    ));
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"UPDATE \"blocks\" SET \"deleted_at\""#));
+    assert!(!log.contains("DELETE"));
+}
+
+#[test]
+fn block_status_is_resolved_and_validated() {
+    let future = DateTimeWithTimeZone::from(Utc::now() + Duration::hours(1));
+    let past = DateTimeWithTimeZone::from(Utc::now() - Duration::hours(1));
+
+    // 旧客户端只提交 draft
+    let legacy = BlockModel {
+        draft: Some(true),
+        ..Default::default()
+    };
+    assert_eq!(legacy.resolved_status(), Some(BlockStatus::Draft));
+    let scheduled = BlockModel {
+        publish_at: Some(future),
+        ..Default::default()
+    };
+    assert_eq!(scheduled.resolved_status(), Some(BlockStatus::Scheduled));
+    assert!(scheduled.validate().is_ok());
+    assert_eq!(BlockModel::default().resolved_status(), None);
+
+    for invalid in [
+        BlockModel {
+            status: Some(BlockStatus::Scheduled),
+            ..Default::default()
+        },
+        BlockModel {
+            publish_at: Some(past),
+            ..Default::default()
+        },
+        BlockModel {
+            status: Some(BlockStatus::Published),
+            publish_at: Some(future),
+            ..Default::default()
+        },
+    ] {
+        assert!(invalid.validate().is_err());
     }
 }
+
+#[tokio::test]
+async fn only_drafts_can_be_autosaved() {
+    let owner = block_id(100);
+    let mut draft = prepare::block(1, "Draft");
+    draft.status = "draft".to_owned();
+    draft.draft = Some(true);
+    draft.published_at = None;
+    let mut saved = draft.clone();
+    saved.context = Some("Autosaved".to_owned());
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[prepare::block(1, "Published")], [draft], [saved]])
+        .into_connection();
+    let form = || AutosaveModel {
+        context: Some("Autosaved".to_owned()),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        BlockServices::autosave_for_owner(&db, block_id(1), owner, form()).await,
+        Err(ServiceError::Invalid(_))
+    ));
+    let block = BlockServices::autosave_for_owner(&db, block_id(1), owner, form())
+        .await
+        .unwrap();
+    assert_eq!(block.context.as_deref(), Some("Autosaved"));
+
+    // 自动保存只写入提交的字段和更新时间，不保存历史版本
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"UPDATE \"blocks\" SET \"context\" = $1, \"update_time\" = $2 WHERE"#));
+    assert!(!log.contains("block_revisions"));
+}
+
+#[tokio::test]
+async fn patching_with_a_version_from_before_autosave_is_rejected() {
+    let owner = block_id(100);
+    let mut draft = prepare::block(1, "Draft");
+    draft.status = "draft".to_owned();
+    draft.draft = Some(true);
+    draft.published_at = None;
+    let mut saved = draft.clone();
+    saved.context = Some("Autosaved".to_owned());
+    saved.update_time = draft.update_time + chrono::Duration::seconds(1);
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[draft.clone()], [saved.clone()], [saved.clone()], [saved]])
+        .into_connection();
+
+    let autosaved = BlockServices::autosave_for_owner(
+        &db,
+        block_id(1),
+        owner,
+        AutosaveModel {
+            context: Some("Autosaved".to_owned()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_ne!(autosaved.update_time, draft.update_time);
+
+    // 客户端仍持有自动保存之前的 ETag，PATCH 返回 412 而不会覆盖自动保存的内容
+    let patch = BlockPatch {
+        context: Patch::Value("Stale edit".to_owned()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        BlockServices::patch_block_for_owner(
+            &db,
+            block_id(1),
+            owner,
+            Some(draft.update_time),
+            patch
+        )
+        .await,
+        Err(ServiceError::PreconditionFailed(_))
+    ));
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(!log.contains("Stale edit"));
+}
+
+#[tokio::test]
+async fn restoring_from_trash_changes_the_version() {
+    let mut trashed = prepare::block(1, "Context A");
+    trashed.deleted_at = Some(trashed.update_time);
+    let mut restored = trashed.clone();
+    restored.deleted_at = None;
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[trashed], [restored]])
+        .into_connection();
+
+    BlockServices::restore_block_for_owner(&db, block_id(1), block_id(100))
+        .await
+        .unwrap();
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"SET \"update_time\" = $1, \"deleted_at\" = $2 WHERE"#));
+}
+
+#[tokio::test]
+async fn scheduled_blocks_are_published_when_due() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 2,
+        }])
+        .into_connection();
+
+    let result = BlockServices::publish_due(&db, Utc::now().into())
+        .await
+        .unwrap();
+    assert_eq!(result.rows_affected, 2);
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(r#"\"published_at\" = COALESCE(\"published_at\", \"publish_at\")"#));
+    assert!(log.contains(r#"\"blocks\".\"status\" = $4 AND \"blocks\".\"publish_at\" <= $5"#));
+}
+
+#[test]
+fn patch_fields_distinguish_missing_from_null() {
+    let patch: BlockPatch = serde_json::from_value(serde_json::json!({
+        "context": null,
+        "location": "Home",
+    }))
+    .unwrap();
+    assert_eq!(patch.context, Patch::Null);
+    assert_eq!(patch.imgs, Patch::Missing);
+    assert_eq!(patch.location, Patch::Value("Home".to_owned()));
+    assert!(patch.validate().is_ok());
+
+    // 只校验提交的值，经纬度必须成对提交
+    for invalid in [
+        serde_json::json!({ "imgs": ["ftp://example.com/a.jpg"] }),
+        serde_json::json!({ "latitude": 30.0 }),
+        serde_json::json!({ "latitude": 91.0, "longitude": 0.0 }),
+    ] {
+        let patch: BlockPatch = serde_json::from_value(invalid).unwrap();
+        assert!(patch.validate().is_err());
+    }
+    let patch: UserPatch = serde_json::from_value(serde_json::json!({ "email": "nope" })).unwrap();
+    assert!(patch.validate().is_err());
+}
+
+#[tokio::test]
+async fn patching_a_block_only_updates_supplied_columns() {
+    let mut block = prepare::block(1, "Context A");
+    block.location = Some("Home".to_owned());
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[block.clone()], [block.clone()]])
+        .append_query_results([[BTreeMap::from([("revision", Value::Int(None))])]])
+        .append_query_results([[prepare::block(1, "New Context A")]])
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 1,
+        }])
+        .into_connection();
+    let patch = BlockPatch {
+        context: Patch::Value("New Context A".to_owned()),
+        location: Patch::Null,
+        ..Default::default()
+    };
+
+    BlockServices::patch_block_for_owner(
+        &db,
+        block_id(1),
+        block_id(100),
+        Some(block.update_time),
+        patch,
+    )
+    .await
+    .unwrap();
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains(
+        r#"UPDATE \"blocks\" SET \"context\" = $1, \"location\" = $2, \"update_time\" = $3 WHERE"#
+    ));
+    assert!(log.contains(r#"INSERT INTO \"block_revisions\""#));
+}
+
+#[tokio::test]
+async fn stale_versions_are_rejected() {
+    let block = prepare::block(1, "Context A");
+    let stale = block.update_time - chrono::Duration::seconds(1);
+    let user = entity::users::Model {
+        id: block_id(100),
+        name: Some("Alice".to_owned()),
+        sex: Some(0),
+        birthday: None,
+        phone: None,
+        email: None,
+        app_id: "openid".to_owned(),
+        role: "user".to_owned(),
+        created_at: block.create_time,
+        updated_at: block.update_time,
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[block.clone()], [block]])
+        .append_query_results([[user]])
+        .into_connection();
+
+    assert!(matches!(
+        BlockServices::patch_block_for_owner(
+            &db,
+            block_id(1),
+            block_id(100),
+            Some(stale),
+            BlockPatch::default()
+        )
+        .await,
+        Err(ServiceError::PreconditionFailed(_))
+    ));
+    assert!(matches!(
+        UserServices::patch_user_by_id(&db, block_id(100), Some(stale), UserPatch::default()).await,
+        Err(ServiceError::PreconditionFailed(_))
+    ));
+
+    // 版本不一致时不保存历史版本也不写入
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(!log.contains("INSERT"));
+    assert!(!log.contains(r#"UPDATE \""#));
+}
+
+#[test]
+fn cursors_round_trip_as_strings() {
+    let block = prepare::block(1, "Context A");
+    let cursor = Cursor::from(&block);
+    let encoded = cursor.to_string();
+    assert_eq!(encoded.parse::<Cursor>().unwrap(), cursor);
+    assert_eq!(
+        serde_json::to_value(cursor).unwrap(),
+        serde_json::json!(encoded)
+    );
+
+    for invalid in ["", "abc", "1704067200000000", "1704067200000000_not-a-uuid"] {
+        assert!(invalid.parse::<Cursor>().is_err());
+    }
+}
+
+#[tokio::test]
+async fn blocks_are_listed_after_the_cursor() {
+    let rows = [3, 2, 1].map(|n| prepare::block(n, "Context"));
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([rows.clone()])
+        .into_connection();
+    let query = BlockQuery {
+        owner: Some(block_id(100)),
+        has_images: Some(true),
+        location: Some("50%".to_owned()),
+        order: SortOrder::Desc,
+        ..Default::default()
+    };
+    let cursor = Cursor::from(&prepare::block(4, "Context"));
+
+    let page = BlockServices::find_blocks(&db, block_id(100), &query, Some(cursor), 1, 2)
+        .await
+        .unwrap();
+    // 多取一条判断是否还有后续数据，下一页从本页最后一条开始
+    assert_eq!(page.rows.len(), 2);
+    assert_eq!(page.num_pages, None);
+    assert_eq!(page.next_cursor, Some(Cursor::from(&rows[1])));
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("(create_time, id) < ($"));
+    assert!(
+        log.contains(r#"ORDER BY \"blocks\".\"create_time\" DESC, \"blocks\".\"id\" DESC LIMIT"#)
+    );
+    assert!(log.contains("COALESCE(json_array_length(imgs), 0) > 0"));
+    assert!(log.contains(r#"location ILIKE $"#));
+    assert!(log.contains(r#"String(Some("%50\\%%"))"#));
+    assert!(log.contains(r#"String(Some("00000000-0000-0000-0000-000000000064"))"#));
+}
+
+#[tokio::test]
+async fn page_zero_is_treated_as_the_first_page() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[BTreeMap::from([("num_items", Value::BigInt(Some(3)))])]])
+        .append_query_results([[
+            prepare::block(1, "Context A"),
+            prepare::block(2, "Context B"),
+        ]])
+        .into_connection();
+
+    let page =
+        BlockServices::find_blocks_by_pid(&db, block_id(100), &BlockQuery::default(), None, 0, 2)
+            .await
+            .unwrap();
+    assert_eq!(page.num_pages, Some(2));
+    assert_eq!(
+        page.next_cursor,
+        Some(Cursor::from(&prepare::block(2, "Context B")))
+    );
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("OFFSET"));
+    assert!(log.contains("BigUnsigned(Some(0))"));
+}
+
+#[tokio::test]
+async fn timeline_counts_own_blocks_per_period() {
+    let row = |period: &str, count: i64| {
+        BTreeMap::from([
+            ("period", Value::String(Some(Box::new(period.to_owned())))),
+            ("count", Value::BigInt(Some(count))),
+        ])
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[row("2024-01", 3), row("2024-02", 1)]])
+        .into_connection();
+    let query = TimelineQuery {
+        granularity: Granularity::Month,
+        tz_offset: Some(480),
+        ..Default::default()
+    };
+
+    let buckets = BlockServices::timeline(&db, block_id(100), &query)
+        .await
+        .unwrap();
+    assert_eq!(
+        buckets,
+        [
+            TimelineBucket {
+                period: "2024-01".to_owned(),
+                count: 3
+            },
+            TimelineBucket {
+                period: "2024-02".to_owned(),
+                count: 1
+            },
+        ]
+    );
+
+    let log = format!("{:?}", db.into_transaction_log());
+    assert!(log.contains("make_interval(mins => $1)"));
+    assert!(log.contains(r#"Int(Some(480)), String(Some("YYYY-MM"))"#));
+    assert!(log.contains(r#"\"blocks\".\"pid\" = $3"#));
+    assert!(
+        log.contains(r#"\"blocks\".\"deleted_at\" IS NULL GROUP BY period ORDER BY period ASC"#)
+    );
+}
@@ -1,50 +1,53 @@
-// use ::entity::post;
-use sea_orm::*;
+#![cfg(feature = "mock")]
+
+use ::entity::blocks;
+use chrono::{TimeZone, Utc};
+use sea_orm::{prelude::DateTimeWithTimeZone, sqlx::types::uuid, *};
+use std::collections::BTreeMap;
+
+pub fn block_id(n: u128) -> uuid::Uuid {
+    uuid::Uuid::from_u128(n)
+}
+
+pub fn block(n: u128, context: &str) -> blocks::Model {
+    let time = DateTimeWithTimeZone::from(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+    blocks::Model {
+        id: block_id(n),
+        pid: Some(block_id(100).to_string()),
+        context: Some(context.to_owned()),
+        imgs: None,
+        location: None,
+        latitude: None,
+        longitude: None,
+        draft: Some(false),
+        visibility: "private".to_owned(),
+        status: "published".to_owned(),
+        publish_at: None,
+        published_at: Some(time),
+        create_time: time,
+        update_time: time,
+        deleted_at: None,
+    }
+}
 
-#[cfg(feature = "mock")]
 pub fn prepare_mock_db() -> DatabaseConnection {
     MockDatabase::new(DatabaseBackend::Postgres)
         .append_query_results([
-            [post::Model {
-                id: 1,
-                title: "Title A".to_owned(),
-                text: "Text A".to_owned(),
-            }],
-            [post::Model {
-                id: 5,
-                title: "Title C".to_owned(),
-                text: "Text C".to_owned(),
-            }],
-            [post::Model {
-                id: 6,
-                title: "Title D".to_owned(),
-                text: "Text D".to_owned(),
-            }],
-            [post::Model {
-                id: 1,
-                title: "Title A".to_owned(),
-                text: "Text A".to_owned(),
-            }],
-            [post::Model {
-                id: 1,
-                title: "New Title A".to_owned(),
-                text: "New Text A".to_owned(),
-            }],
-            [post::Model {
-                id: 5,
-                title: "Title C".to_owned(),
-                text: "Text C".to_owned(),
-            }],
+            [block(1, "Context A")],
+            [block(5, "Context C")],
+            [block(6, "Context D")],
+            [block(1, "Context A")],
         ])
-        .append_exec_results([
+        // 修改前保存历史版本：查询最新版本号
+        .append_query_results([[BTreeMap::from([("revision", Value::Int(None))])]])
+        .append_query_results([[block(1, "New Context A")]])
+        // 写入历史版本，删除时移入回收站
+        .append_exec_results(vec![
             MockExecResult {
-                last_insert_id: 6,
+                last_insert_id: 0,
                 rows_affected: 1,
-            },
-            MockExecResult {
-                last_insert_id: 6,
-                rows_affected: 5,
-            },
+            };
+            2
         ])
         .into_connection()
 }
@@ -0,0 +1,93 @@
+use service::{
+    block::BlockModel, sea_orm::sqlx::types::uuid, search_history::SearchHistoryModel,
+    user::UserModel, validation::parse_lat_lng,
+};
+use validator::Validate;
+
+fn user(sex: &str, email: &str, phone: &str) -> UserModel {
+    UserModel {
+        id: uuid::Uuid::new_v4(),
+        name: "leaf".to_string(),
+        sex: sex.to_string(),
+        email: email.to_string(),
+        phone: phone.to_string(),
+        birthday: None,
+    }
+}
+
+fn block(imgs: Vec<&str>, lat_lng: &str) -> BlockModel {
+    BlockModel {
+        context: Some("context".to_string()),
+        imgs: Some(imgs.into_iter().map(String::from).collect()),
+        location: None,
+        latitude_and_longitude: Some(lat_lng.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn user_rules() {
+    assert!(user("1", "leaf@example.com", "13800138000")
+        .validate()
+        .is_ok());
+
+    let errors = user("male", "not-an-email", "12ab").validate().unwrap_err();
+    let fields = errors.field_errors();
+    assert!(fields.contains_key("sex"));
+    assert!(fields.contains_key("email"));
+    assert!(fields.contains_key("phone"));
+}
+
+#[test]
+fn block_rules() {
+    assert!(
+        block(vec!["https://cdn.example.com/a.png"], "31.23, 121.47")
+            .validate()
+            .is_ok()
+    );
+
+    let errors = block(vec!["javascript:alert(1)"], "91,0")
+        .validate()
+        .unwrap_err();
+    let fields = errors.field_errors();
+    assert!(fields.contains_key("imgs"));
+    assert!(fields.contains_key("latitude_and_longitude"));
+
+    let too_many = block(vec!["https://cdn.example.com/a.png"; 10], "0,0");
+    assert!(too_many.validate().is_err());
+}
+
+#[test]
+fn search_history_rules() {
+    let query = |q: &str| SearchHistoryModel {
+        query: q.to_string(),
+    };
+    assert!(query("coffee").validate().is_ok());
+    assert!(query("   ").validate().is_err());
+    assert!(query(&"茶".repeat(101)).validate().is_err());
+}
+
+#[test]
+fn lat_lng_parsing() {
+    assert_eq!(parse_lat_lng("31.5,121.25"), Some((31.5, 121.25)));
+    assert_eq!(parse_lat_lng("31.5"), None);
+    assert_eq!(parse_lat_lng("0,181"), None);
+}
+
+#[test]
+fn coordinates_must_be_paired() {
+    let form = BlockModel {
+        latitude: Some(31.2),
+        ..Default::default()
+    };
+    assert!(form.validate().is_err());
+
+    let form = BlockModel {
+        latitude: Some(31.2),
+        longitude: Some(121.4),
+        latitude_and_longitude: Some("0,0".to_string()),
+        ..Default::default()
+    };
+    assert!(form.validate().is_ok());
+    assert_eq!(form.coordinates(), Some((31.2, 121.4)));
+}